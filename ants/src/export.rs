@@ -0,0 +1,156 @@
+//! Snapshots of the marker intensity fields, exported with <kbd>X</kbd> or
+//! [`export`].
+//!
+//! Each field is saved twice: as a grayscale PNG for a quick look, and as a
+//! raw file keeping the actual intensities. The raw file is little-endian
+//! and starts with a header:
+//!
+//! | bytes | type    | value                                  |
+//! |-------|---------|----------------------------------------|
+//! | 4     | `[u8]`  | magic `ANTF`                           |
+//! | 2     | `u16`   | format version, `1`                    |
+//! | 1     | `u8`    | phase, `0` to food and `1` to home     |
+//! | 4     | `f32`   | sim time in seconds                    |
+//! | 4     | `u32`   | cell size in pixels                    |
+//! | 4     | `u32`   | field width `w` in cells               |
+//! | 4     | `u32`   | field height `h` in cells              |
+//!
+//! followed by `w × h` `f32` intensities, row by row from the top left. The
+//! intensity of a cell is the sum of the markers inside it.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use image::{ColorType, ImageError};
+
+use crate::sim::{MarkerType, World};
+
+const MAGIC: &[u8; 4] = b"ANTF";
+const VERSION: u16 = 1;
+/// Percentile of the marked cells drawn white in the PNG, so food source
+/// markers don't flatten the trails
+const PNG_REFERENCE_PERCENTILE: f32 = 0.99;
+
+/// Marker intensity of one type, summed per cell
+pub struct FieldSnapshot {
+    pub marker_type: MarkerType,
+    pub time: f32,
+    pub cell_size: usize,
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f32>,
+}
+
+impl FieldSnapshot {
+    /// Rasterizes the markers of `marker_type` on cells of `cell_size`
+    /// pixels, `1` for one value per pixel
+    pub fn new(world: &World, marker_type: MarkerType, cell_size: usize, time: f32) -> Self {
+        let cell_size = cell_size.max(1);
+        let width = world.width.div_ceil(cell_size);
+        let height = world.height.div_ceil(cell_size);
+
+        let mut values = vec![0.0; width * height];
+        for marker in world.get_grid(marker_type).iter() {
+            let (x, y) = (
+                marker.position.x as usize / cell_size,
+                marker.position.y as usize / cell_size,
+            );
+            if marker.position.x >= 0.0 && marker.position.y >= 0.0 && x < width && y < height {
+                values[y * width + x] += marker.intensity.max(0.0);
+            }
+        }
+
+        Self {
+            marker_type,
+            time,
+            cell_size,
+            width,
+            height,
+            values,
+        }
+    }
+
+    /// Intensity at `percentile` of the marked cells, `0` without markers
+    pub fn percentile(&self, percentile: f32) -> f32 {
+        let mut marked: Vec<f32> = self.values.iter().copied().filter(|v| *v > 0.0).collect();
+        marked.sort_by(f32::total_cmp);
+        marked
+            .get((marked.len() as f32 * percentile) as usize)
+            .or(marked.last())
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Grayscale levels of the field row by row from the top left,
+    /// saturated from the reference percentile
+    pub fn levels(&self) -> Vec<u8> {
+        let max = self.percentile(PNG_REFERENCE_PERCENTILE);
+        self.values
+            .iter()
+            .map(|value| {
+                if max > 0.0 {
+                    ((value / max).min(1.0) * 255.0).round() as u8
+                } else {
+                    0
+                }
+            })
+            .collect()
+    }
+
+    pub fn save_png(&self, path: &str) -> io::Result<()> {
+        image::save_buffer(
+            path,
+            &self.levels(),
+            self.width as u32,
+            self.height as u32,
+            ColorType::L8,
+        )
+        .map_err(|err| match err {
+            ImageError::IoError(err) => err,
+            err => io::Error::other(err),
+        })
+    }
+
+    pub fn write_raw(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&[match self.marker_type {
+            MarkerType::ToFood => 0,
+            MarkerType::ToHome => 1,
+        }])?;
+        out.write_all(&self.time.to_le_bytes())?;
+        out.write_all(&(self.cell_size as u32).to_le_bytes())?;
+        out.write_all(&(self.width as u32).to_le_bytes())?;
+        out.write_all(&(self.height as u32).to_le_bytes())?;
+        for value in &self.values {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn save_raw(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_raw(&mut out)?;
+        out.flush()
+    }
+}
+
+/// Saves both fields as `<prefix>_to_food` and `<prefix>_to_home`, each
+/// with a `.png` and a `.f32` file. Returns the paths written.
+pub fn export(world: &World, prefix: &str, cell_size: usize, time: f32) -> io::Result<Vec<String>> {
+    let mut paths = vec![];
+    for (marker_type, name) in [
+        (MarkerType::ToFood, "to_food"),
+        (MarkerType::ToHome, "to_home"),
+    ] {
+        let snapshot = FieldSnapshot::new(world, marker_type, cell_size, time);
+        let path = format!("{}_{}", prefix, name);
+        snapshot.save_png(&format!("{}.png", path))?;
+        snapshot.save_raw(&format!("{}.f32", path))?;
+        paths.push(format!("{}.png", path));
+        paths.push(format!("{}.f32", path));
+    }
+    Ok(paths)
+}
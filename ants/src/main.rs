@@ -1,15 +1,31 @@
 use std::f32::consts::PI;
 
 use macroquad::prelude::*;
+use replay::{Input, InputRecorder, Replay, Toggle};
 use sim::*;
+use simulation::Simulation;
+use stats::Stats;
+use tools::Palette;
+use trails::Trails;
 
+mod export;
+mod recording;
+mod replay;
+mod scenario;
+mod schedule;
 mod sim;
+mod simulation;
+mod stats;
+mod sweep;
+mod tools;
+mod trails;
 
 fn window_conf() -> Conf {
     Conf {
         window_title: "Ants".to_owned(),
-        window_width: 1180,
-        window_height: 720,
+        window_width: WORLD_WIDTH as i32,
+        window_height: WORLD_HEIGHT as i32,
+        window_resizable: true,
         ..Default::default()
     }
 }
@@ -18,8 +34,161 @@ pub struct Config {
     draw_grid: bool,
     draw_ants: bool,
     draw_markers: bool,
+    draw_stats: bool,
+    draw_trails: bool,
+    move_speed: f32,
+    marker_detection_radius: f32,
+    marker_period: f32,
+    direction_noise: f32,
+    direction_update_period: f32,
+    path_integration: bool,
+    path_integration_noise: f32,
+    path_integration_weight: f32,
+    wind: WindMode,
+    wind_strength: f32,
+    wind_direction: f32,
+    wind_ant_bias: f32,
+    food_regrowth: f32,
+    food_spawn_rate: f32,
+    food_spawn_patchiness: f32,
+    crowding: bool,
+    separation_radius: f32,
+    separation_strength: f32,
+    max_ants_per_cell: f32,
+    egg_laying_rate: f32,
+    egg_duration: f32,
+    larva_food: f32,
+    energy: bool,
+    energy_cost: f32,
+    energy_return: f32,
+    wall_following: bool,
+    wall_probe_distance: f32,
+    wall_follow_time: f32,
+    day_night: bool,
+    day_length: f32,
+    night_activity: f32,
+    night_evaporation: f32,
+    night_noise: f32,
+    levy_flight: bool,
+    levy_exponent: f32,
+    levy_min_run: f32,
+    map_seed: f32,
+    map_scale: f32,
+    map_obstacles: f32,
+    map_terrain: f32,
+    map_food_patches: f32,
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            draw_grid: false,
+            draw_ants: true,
+            draw_markers: true,
+            draw_stats: true,
+            draw_trails: false,
+            move_speed: MOVE_SPEED,
+            marker_detection_radius: MARKER_DETECTION_RADIUS,
+            marker_period: MARKER_PERIOD,
+            direction_noise: DIRECTION_NOISE,
+            direction_update_period: DIRECTION_UPDATE_PERIOD,
+            path_integration: false,
+            path_integration_noise: PATH_INTEGRATION_NOISE,
+            path_integration_weight: PATH_INTEGRATION_WEIGHT,
+            wind: WindMode::Off,
+            wind_strength: WIND_STRENGTH,
+            wind_direction: 0.0,
+            wind_ant_bias: WIND_ANT_BIAS,
+            food_regrowth: 0.0,
+            food_spawn_rate: 0.0,
+            food_spawn_patchiness: 0.0,
+            crowding: false,
+            separation_radius: SEPARATION_RADIUS,
+            separation_strength: SEPARATION_STRENGTH,
+            max_ants_per_cell: 0.0,
+            egg_laying_rate: EGG_LAYING_RATE,
+            egg_duration: EGG_DURATION,
+            larva_food: LARVA_FOOD,
+            energy: false,
+            energy_cost: ENERGY_COST,
+            energy_return: ENERGY_RETURN,
+            wall_following: false,
+            wall_probe_distance: WALL_PROBE_DISTANCE,
+            wall_follow_time: WALL_FOLLOW_TIME,
+            day_night: false,
+            day_length: DAY_LENGTH,
+            night_activity: NIGHT_ACTIVITY,
+            night_evaporation: NIGHT_EVAPORATION,
+            night_noise: NIGHT_NOISE,
+            levy_flight: false,
+            levy_exponent: LEVY_EXPONENT,
+            levy_min_run: LEVY_MIN_RUN,
+            map_seed: 0.0,
+            map_scale: MAP_SCALE,
+            map_obstacles: MAP_OBSTACLES,
+            map_terrain: MAP_TERRAIN,
+            map_food_patches: MAP_FOOD_PATCHES,
+        }
+    }
+}
+
+/// Generates the runtime parameter list and its setter from one table
+macro_rules! params {
+    ($($name:ident),* $(,)?) => {
+        impl Config {
+            /// Simulation parameters that can be changed at runtime by name
+            pub const PARAMS: &'static [&'static str] = &[$(stringify!($name)),*];
+
+            /// Sets a parameter by name, returns `false` if it does not exist
+            pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+                let param = match name {
+                    $(stringify!($name) => &mut self.$name,)*
+                    _ => return false,
+                };
+                *param = value;
+                true
+            }
+        }
+    };
+}
+
+params!(
+    move_speed,
+    marker_detection_radius,
+    marker_period,
+    direction_noise,
+    direction_update_period,
+    path_integration_noise,
+    path_integration_weight,
+    wind_strength,
+    wind_direction,
+    wind_ant_bias,
+    food_regrowth,
+    food_spawn_rate,
+    food_spawn_patchiness,
+    separation_radius,
+    separation_strength,
+    max_ants_per_cell,
+    egg_laying_rate,
+    egg_duration,
+    larva_food,
+    energy_cost,
+    energy_return,
+    wall_probe_distance,
+    wall_follow_time,
+    day_length,
+    night_activity,
+    night_evaporation,
+    night_noise,
+    levy_exponent,
+    levy_min_run,
+    map_seed,
+    map_scale,
+    map_obstacles,
+    map_terrain,
+    map_food_patches,
+);
+
 const MAX_RESERVE: f32 = 2000.0;
 const DIRECTION_UPDATE_PERIOD: f32 = 0.125;
 const MOVE_SPEED: f32 = 50.0;
@@ -28,47 +197,167 @@ const MARKER_PERIOD: f32 = 0.25;
 const DIRECTION_NOISE: f32 = PI * 0.1;
 const ROTATION_SPEED: f32 = 10.0;
 const MAX_MARKER_PER_CELL: usize = 1024;
-const MAX_ANTS: usize = 512;
-#[macroquad::main(window_conf)]
-async fn main() {
-    println!("{} {}", screen_width(), screen_height());
-
-    let ant_texture = load_texture("assets/ant.png").await.unwrap();
-    let mut colony = Colony::new(vec2(screen_width() * 0.2, screen_height() * 0.5));
-    let mut world = World::new(screen_width() as usize, screen_height() as usize);
-    world.add_marker(Marker::new(
-        colony.position,
-        MarkerType::ToHome,
-        100.0,
-        true,
-    ));
-
-    for x in 0..10 {
-        for y in 0..10 {
-            world.add_food(Food::new(
-                vec2(
-                    screen_width() * 0.8 + (x as f32 * 4.),
-                    screen_height() * 0.5 + (y as f32 * 4.),
-                ),
-                4.,
-                10.,
-            ));
+const MARKER_CELL_SIZE: usize = 45;
+/// Cells of the fields ants sense markers on, small next to the detection
+/// radius so the field stays close to the markers themselves
+const MARKER_FIELD_CELL_SIZE: usize = 10;
+const MARKER_MERGE_RADIUS: f32 = 5.0;
+const ANT_COUNT: usize = 512;
+const ANT_COUNT_STEP: usize = 100;
+const ANT_COLOR: Color = RED;
+/// Pixels walked per cycle of the walking animation
+const ANT_STRIDE: f32 = 4.0;
+const MAX_HANDLING_WAIT: f32 = 10.0;
+const FOOD_COOLDOWN: f32 = 2.0;
+const TERRAIN_CELL_SIZE: usize = 10;
+/// Distance kept between the nest and the world edges
+const NEST_MARGIN: f32 = 10.0;
+const WORLD_WIDTH: usize = 1180;
+const WORLD_HEIGHT: usize = 720;
+const TIME_STEP: f32 = 0.016;
+const PATH_INTEGRATION_NOISE: f32 = 0.1;
+const PATH_INTEGRATION_WEIGHT: f32 = 0.5;
+const WIND_STRENGTH: f32 = 5.0;
+const WIND_ANT_BIAS: f32 = 0.2;
+const WIND_NOISE_SCALE: f32 = 0.004;
+const WIND_NOISE_SPEED: f32 = 0.05;
+const FOOD_SPAWN_NOISE_SCALE: f32 = 0.005;
+const MAX_SPAWNED_CLUSTERS: usize = 30;
+const SEPARATION_RADIUS: f32 = 6.0;
+const SEPARATION_STRENGTH: f32 = 40.0;
+const EGG_LAYING_RATE: f32 = 0.5;
+const EGG_COST: f32 = 1.0;
+const EGG_DURATION: f32 = 10.0;
+const LARVA_FOOD: f32 = 4.0;
+const LARVA_FEEDING_RATE: f32 = 0.5;
+const MAX_ENERGY: f32 = 100.0;
+const ENERGY_PER_FOOD: f32 = 50.0;
+const ENERGY_COST: f32 = 0.01;
+const ENERGY_RETURN: f32 = 0.3;
+const WALL_PROBE_DISTANCE: f32 = 10.0;
+const WALL_FOLLOW_TIME: f32 = 1.5;
+const DAY_LENGTH: f32 = 120.0;
+const NIGHT_ACTIVITY: f32 = 0.2;
+const NIGHT_EVAPORATION: f32 = 0.5;
+const NIGHT_NOISE: f32 = 2.0;
+const NIGHT_DARKNESS: f32 = 0.6;
+const DISCOVERY_RADIUS: f32 = 60.0;
+const LEVY_EXPONENT: f32 = 2.0;
+const LEVY_MIN_RUN: f32 = 10.0;
+const LEVY_MAX_RUN: f32 = 2000.0;
+const MAP_SCALE: f32 = 150.0;
+const MAP_OBSTACLES: f32 = 0.15;
+const MAP_TERRAIN: f32 = 0.3;
+const MAP_FOOD_PATCHES: f32 = 6.0;
+const MAP_NEST_CLEARING: f32 = 50.0;
+const MAP_PATCH_SPACING: f32 = 120.0;
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
+
+/// Camera fitting the whole world in the window, keeping its aspect ratio
+fn world_camera(world: &World) -> Camera2D {
+    let size = vec2(world.width as f32, world.height as f32);
+    let scale = (screen_width() / size.x).min(screen_height() / size.y);
+    Camera2D {
+        target: size * 0.5,
+        zoom: vec2(2.0 * scale / screen_width(), -2.0 * scale / screen_height()),
+        ..Default::default()
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("sweep") {
+        if let Err(err) = sweep::run(&args[2..]) {
+            eprintln!("sweep: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("trajectory") {
+        let Some(path) = args.get(2) else {
+            eprintln!("usage: ants trajectory <file>");
+            std::process::exit(1);
+        };
+        if let Err(err) = recording::summarize(path) {
+            eprintln!("trajectory: {}: {}", path, err);
+            std::process::exit(1);
         }
+        return;
+    }
+
+    // built before the window opens, so bad options fail right away
+    let sim = match simulation(&args) {
+        Ok(sim) => sim,
+        Err(err) => {
+            eprintln!("ants: {}", err);
+            std::process::exit(1);
+        }
+    };
+    macroquad::Window::from_config(window_conf(), run(sim));
+}
+
+/// Simulation replayed from `--replay`, or started from the options
+fn simulation(args: &[String]) -> Result<Simulation, String> {
+    if let Some(path) = arg_value(args, "--replay") {
+        let replay = Replay::load(path).map_err(|err| format!("{}: {}", path, err))?;
+        return Simulation::from_replay(replay);
     }
 
-    let mut cfg = Config {
-        draw_grid: false,
-        draw_ants: true,
-        draw_markers: true,
+    let seed = match arg_value(args, "--seed") {
+        Some(seed) => seed
+            .parse()
+            .map_err(|_| format!("invalid seed `{}`", seed))?,
+        None => miniquad::date::now() as u64,
     };
+    rand::srand(seed);
+    let mut sim = Simulation::from_args(args)?;
+    if let Some(path) = arg_value(args, "--record-inputs") {
+        let args = replay::recorded_args(args);
+        sim.inputs = Some(
+            InputRecorder::create(path, seed, &args).map_err(|err| format!("{}: {}", path, err))?,
+        );
+    }
+    Ok(sim)
+}
+
+async fn run(mut sim: Simulation) {
+    // room for a full batch of ants in a single draw call
+    gl_set_drawcall_buffer_capacity(ANT_BATCH_SIZE * 8, ANT_BATCH_SIZE * 12);
+    let ant_texture = load_texture("assets/ant_walk.png").await.unwrap();
+    let mut palette = Palette::default();
+    let mut stats = Stats::default();
+    let mut trails = Trails::default();
 
     loop {
         clear_background(BLACK);
 
         //let render_timer = SystemTime::now();
 
-        world.draw(&cfg);
-        colony.draw(ant_texture, &cfg);
+        let camera = world_camera(&sim.world);
+        let mouse_pos = camera.screen_to_world(mouse_position().into());
+        set_camera(&camera);
+        sim.world.draw(&sim.cfg);
+        sim.colony.draw(ant_texture, &sim.cfg);
+        if sim.cfg.draw_trails {
+            trails.draw();
+        }
+        let size = vec2(sim.world.width as f32, sim.world.height as f32);
+        sim.world.day.draw(size, &sim.cfg);
+        palette.draw_preview(mouse_pos);
+        set_default_camera();
+        palette.draw_buttons();
+        if sim.cfg.draw_stats {
+            stats.draw();
+        }
+        if sim.cfg.draw_trails {
+            trails.draw_report();
+        }
         /*draw_text(
             &format!(
                 "render: {:.3}ms",
@@ -80,8 +369,11 @@ async fn main() {
             WHITE,
         );
         let update_timer = SystemTime::now();*/
-        colony.update(0.016, &mut world);
-        world.update(0.016);
+        sim.step();
+        stats.update(TIME_STEP, &sim.world, &sim.colony);
+        if sim.cfg.draw_trails {
+            trails.update(TIME_STEP, &sim.world, &sim.colony);
+        }
         /*draw_text(
             &format!(
                 "update: {:.3}ms",
@@ -100,20 +392,101 @@ async fn main() {
             20.0,
             WHITE,
         );
+        draw_text(
+            &format!(
+                "time: {:.1}s ants: {} eggs: {} larvae: {} food: {:.1} homing: {} wind: {:?} crowding: {}",
+                sim.time,
+                sim.colony.population(),
+                sim.colony.eggs(),
+                sim.colony.larvae(),
+                sim.colony.food,
+                sim.cfg.path_integration,
+                sim.cfg.wind,
+                sim.cfg.crowding
+            ),
+            20.0,
+            50.0,
+            20.0,
+            WHITE,
+        );
 
-        if is_key_pressed(KeyCode::G) {
-            cfg.draw_grid = !cfg.draw_grid;
+        // live inputs wait for the end of a replay
+        let mut inputs = vec![];
+        for (toggle, key) in Toggle::ALL.iter().zip(Toggle::KEYS) {
+            if is_key_pressed(key) {
+                inputs.push(Input::Toggle(*toggle));
+            }
+        }
+        if is_key_pressed(KeyCode::RightBracket) {
+            inputs.push(Input::Population(sim.colony.population() + ANT_COUNT_STEP));
+        }
+        if is_key_pressed(KeyCode::LeftBracket) {
+            inputs.push(Input::Population(
+                sim.colony.population().saturating_sub(ANT_COUNT_STEP),
+            ));
+        }
+        if is_key_pressed(KeyCode::W) {
+            inputs.push(Input::Wind);
         }
-        if is_key_pressed(KeyCode::A) {
-            cfg.draw_ants = !cfg.draw_ants;
+        let (width, height) = (screen_width() as usize, screen_height() as usize);
+        // fit the world to the window, unless minimized
+        if is_key_pressed(KeyCode::R) && World::check_size(width, height).is_ok() {
+            inputs.push(Input::Resize(width, height));
         }
-        if is_key_pressed(KeyCode::M) {
-            cfg.draw_markers = !cfg.draw_markers;
+        if !sim.is_replaying() {
+            for input in inputs {
+                sim.input(input);
+            }
         }
+        if is_key_pressed(KeyCode::X) {
+            // per pixel, or per terrain cell with shift
+            let cell_size = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                TERRAIN_CELL_SIZE
+            } else {
+                1
+            };
+            let prefix = format!("fields_{:.0}s", sim.time);
+            match export::export(&sim.world, &prefix, cell_size, sim.time) {
+                Ok(paths) => println!("exported {}", paths.join(", ")),
+                Err(err) => eprintln!("export: {}", err),
+            }
+        }
+
+        draw_text(
+            &format!(
+                "markers merge: {} merged: {} dropped: {} levy flight: {} food found: {} after {:.1}s",
+                sim.world.merge_markers,
+                sim.world.markers_merged,
+                sim.world.markers_dropped,
+                sim.cfg.levy_flight,
+                sim.world.discoveries,
+                sim.world.mean_discovery_time().unwrap_or(0.0)
+            ),
+            20.0,
+            65.0,
+            20.0,
+            WHITE,
+        );
+        draw_text(
+            &format!(
+                "energy: {} starved: {} wall following: {} day/night: {} {:05.2}h",
+                sim.cfg.energy,
+                sim.colony.starved,
+                sim.cfg.wall_following,
+                sim.cfg.day_night,
+                sim.world.day.hour(&sim.cfg)
+            ),
+            20.0,
+            80.0,
+            20.0,
+            WHITE,
+        );
 
-        if is_mouse_button_pressed(MouseButton::Left) {
-            let mouse_pos = mouse_position();
-            world.add_food(Food::new(vec2(mouse_pos.0, mouse_pos.1), 4., 100.));
+        if palette.update() && !sim.is_replaying() {
+            sim.input(Input::Tool {
+                palette: palette.clone(),
+                position: mouse_pos,
+            });
         }
 
         next_frame().await
@@ -0,0 +1,464 @@
+//! Binary trajectory recordings, written with `--record <file>`.
+//!
+//! All values are little-endian. The file starts with a header:
+//!
+//! | bytes | type    | value                 |
+//! |-------|---------|-----------------------|
+//! | 4     | `[u8]`  | magic `ANTR`          |
+//! | 2     | `u16`   | format version, `2`   |
+//! | 4     | `f32`   | seconds per tick      |
+//! | 4     | `u32`   | world width           |
+//! | 4     | `u32`   | world height          |
+//!
+//! followed by one frame per tick until the end of the file:
+//!
+//! | bytes  | type  | value                                              |
+//! |--------|-------|----------------------------------------------------|
+//! | 4      | `u32` | tick                                               |
+//! | 4      | `u32` | ant count `n`                                      |
+//! | 4      | `u32` | event count `m`                                    |
+//! | 15 × n | ant   | `id: u32, x: f32, y: f32, heading: u16, phase: u8` |
+//! | ...    | event | `m` events                                         |
+//!
+//! Headings are quantized from `[0, 2π)` to the full `u16` range, phases are
+//! `0` looking for food and `1` bringing food home. Ants are told apart by
+//! their id, never reused, as their order changes between frames when ants
+//! die, are removed or hatch. Each event is a `u8` tag followed by its
+//! fields, where `ant` is an id:
+//!
+//! | tag | event          | fields                                            |
+//! |-----|----------------|---------------------------------------------------|
+//! | 0   | food added     | `x: f32, y: f32, kind: u8, quantity: f32`         |
+//! | 1   | food picked    | `ant: u32, x: f32, y: f32, value: f32`            |
+//! | 2   | food delivered | `ant: u32, value: f32`                            |
+//! | 3   | marker added   | `phase: u8, x: f32, y: f32, intensity: f32`       |
+//!
+//! Food kinds are `0` seed, `1` fruit and `2` carcass, marker phases use the
+//! same values as ants.
+
+use std::{
+    collections::HashMap,
+    f32::consts::TAU,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use macroquad::prelude::{vec2, Vec2};
+
+use crate::sim::{Colony, FoodKind, MarkerType, SimEvent, World};
+
+const MAGIC: &[u8; 4] = b"ANTR";
+const VERSION: u16 = 2;
+/// Ticks between flushes, so a closed window loses little of the recording
+const FLUSH_PERIOD: u32 = 64;
+
+pub struct Recorder<W: Write> {
+    out: W,
+    tick: u32,
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create(path: &str, world: &World, time_step: f32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), world, time_step)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W, world: &World, time_step: f32) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&time_step.to_le_bytes())?;
+        out.write_all(&(world.width as u32).to_le_bytes())?;
+        out.write_all(&(world.height as u32).to_le_bytes())?;
+        Ok(Self { out, tick: 0 })
+    }
+
+    /// Writes the state of every ant and the events of one tick
+    pub fn record(&mut self, colony: &Colony, events: &[SimEvent]) -> io::Result<()> {
+        let ants = colony.ants();
+        let mut frame = Vec::with_capacity(12 + ants.len() * 15 + events.len() * 17);
+        frame.extend(self.tick.to_le_bytes());
+        frame.extend((ants.len() as u32).to_le_bytes());
+        frame.extend((events.len() as u32).to_le_bytes());
+
+        for (id, position, heading, phase) in ants.states() {
+            frame.extend(id.to_le_bytes());
+            frame.extend(position.x.to_le_bytes());
+            frame.extend(position.y.to_le_bytes());
+            let heading = (heading.rem_euclid(TAU) / TAU * 65536.0) as u32 as u16;
+            frame.extend(heading.to_le_bytes());
+            frame.push(phase_tag(phase));
+        }
+
+        for event in events {
+            match *event {
+                SimEvent::FoodAdded {
+                    position,
+                    kind,
+                    quantity,
+                } => {
+                    frame.push(0);
+                    push_vec2(&mut frame, position);
+                    frame.push(kind_tag(kind));
+                    frame.extend(quantity.to_le_bytes());
+                }
+                SimEvent::FoodPicked {
+                    ant,
+                    position,
+                    value,
+                } => {
+                    frame.push(1);
+                    frame.extend(ant.to_le_bytes());
+                    push_vec2(&mut frame, position);
+                    frame.extend(value.to_le_bytes());
+                }
+                SimEvent::FoodDelivered { ant, value } => {
+                    frame.push(2);
+                    frame.extend(ant.to_le_bytes());
+                    frame.extend(value.to_le_bytes());
+                }
+                SimEvent::MarkerAdded {
+                    marker_type,
+                    position,
+                    intensity,
+                } => {
+                    frame.push(3);
+                    frame.push(phase_tag(marker_type));
+                    push_vec2(&mut frame, position);
+                    frame.extend(intensity.to_le_bytes());
+                }
+            }
+        }
+
+        self.tick += 1;
+        self.out.write_all(&frame)?;
+        if self.tick.is_multiple_of(FLUSH_PERIOD) {
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Recorded state of a single ant
+#[derive(Clone, Copy, Debug)]
+pub struct AntState {
+    pub id: u32,
+    pub position: Vec2,
+    /// Radians in `[0, 2π)`
+    pub heading: f32,
+    pub phase: MarkerType,
+}
+
+pub struct Frame {
+    pub tick: u32,
+    pub ants: Vec<AntState>,
+    pub events: Vec<SimEvent>,
+}
+
+/// Reads back a recording frame by frame
+pub struct Reader<R: Read> {
+    input: R,
+    pub time_step: f32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Reader<BufReader<File>> {
+    pub fn open(path: &str) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an ant recording"));
+        }
+        let version = u16::from_le_bytes(read_bytes(&mut input)?);
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+
+        Ok(Self {
+            time_step: read_f32(&mut input)?,
+            width: read_u32(&mut input)?,
+            height: read_u32(&mut input)?,
+            input,
+        })
+    }
+
+    /// Reads the next frame, `None` at the end of the recording
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let mut tick = [0; 4];
+        match self.input.read_exact(&mut tick) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let tick = u32::from_le_bytes(tick);
+        let ant_count = read_u32(&mut self.input)?;
+        let event_count = read_u32(&mut self.input)?;
+
+        let input = &mut self.input;
+        let ants = (0..ant_count)
+            .map(|_| {
+                Ok(AntState {
+                    id: read_u32(input)?,
+                    position: read_vec2(input)?,
+                    heading: u16::from_le_bytes(read_bytes(input)?) as f32 / 65536.0 * TAU,
+                    phase: read_phase(input)?,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        let events = (0..event_count)
+            .map(|_| {
+                let [tag] = read_bytes(input)?;
+                Ok(match tag {
+                    0 => SimEvent::FoodAdded {
+                        position: read_vec2(input)?,
+                        kind: match read_bytes(input)? {
+                            [0] => FoodKind::Seed,
+                            [1] => FoodKind::Fruit,
+                            [2] => FoodKind::Carcass,
+                            [kind] => return Err(invalid(&format!("unknown food kind {}", kind))),
+                        },
+                        quantity: read_f32(input)?,
+                    },
+                    1 => SimEvent::FoodPicked {
+                        ant: read_u32(input)?,
+                        position: read_vec2(input)?,
+                        value: read_f32(input)?,
+                    },
+                    2 => SimEvent::FoodDelivered {
+                        ant: read_u32(input)?,
+                        value: read_f32(input)?,
+                    },
+                    3 => SimEvent::MarkerAdded {
+                        marker_type: read_phase(input)?,
+                        position: read_vec2(input)?,
+                        intensity: read_f32(input)?,
+                    },
+                    _ => return Err(invalid(&format!("unknown event {}", tag))),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Some(Frame { tick, ants, events }))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+/// Prints an overview of a recording, `ants trajectory <file>`
+pub fn summarize(path: &str) -> io::Result<()> {
+    let reader = Reader::open(path)?;
+    let time_step = reader.time_step;
+    let (width, height) = (reader.width, reader.height);
+
+    let mut ticks = 0;
+    let mut max_ants = 0;
+    let mut counts = [0usize; 4];
+    let mut delivered = 0.0;
+    let mut walked = 0.0;
+    let mut turned = 0.0;
+    let mut ant_ticks = 0;
+    let mut carrying_ticks = 0;
+    let mut last: HashMap<u32, AntState> = HashMap::new();
+    for frame in reader {
+        let frame = frame?;
+        ticks = frame.tick + 1;
+        max_ants = max_ants.max(frame.ants.len());
+        ant_ticks += frame.ants.len();
+        carrying_ticks += frame
+            .ants
+            .iter()
+            .filter(|ant| ant.phase == MarkerType::ToHome)
+            .count();
+
+        for (ant, previous) in frame
+            .ants
+            .iter()
+            .filter_map(|ant| Some((ant, last.get(&ant.id)?)))
+        {
+            walked += ant.position.distance(previous.position);
+            // shortest angle between the two headings
+            let turn = (ant.heading - previous.heading).rem_euclid(TAU);
+            turned += turn.min(TAU - turn);
+        }
+        last = frame.ants.into_iter().map(|ant| (ant.id, ant)).collect();
+
+        for event in frame.events {
+            let tag = match event {
+                SimEvent::FoodAdded { .. } => 0,
+                SimEvent::FoodPicked { .. } => 1,
+                SimEvent::FoodDelivered { value, .. } => {
+                    delivered += value;
+                    2
+                }
+                SimEvent::MarkerAdded { .. } => 3,
+            };
+            counts[tag] += 1;
+        }
+    }
+
+    println!("world: {}x{}", width, height);
+    println!("ticks: {} ({:.1}s)", ticks, ticks as f32 * time_step);
+    println!("ants: {}", max_ants);
+    println!(
+        "food added: {} picked: {} delivered: {} ({:.1})",
+        counts[0], counts[1], counts[2], delivered
+    );
+    println!("markers added: {}", counts[3]);
+    if ant_ticks > 0 {
+        println!(
+            "carrying: {:.1}%",
+            carrying_ticks as f32 / ant_ticks as f32 * 100.0
+        );
+    }
+    if walked > 0.0 {
+        println!("turning: {:.4} rad/px", turned / walked);
+    }
+    Ok(())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn phase_tag(phase: MarkerType) -> u8 {
+    match phase {
+        MarkerType::ToFood => 0,
+        MarkerType::ToHome => 1,
+    }
+}
+
+fn kind_tag(kind: FoodKind) -> u8 {
+    match kind {
+        FoodKind::Seed => 0,
+        FoodKind::Fruit => 1,
+        FoodKind::Carcass => 2,
+    }
+}
+
+fn push_vec2(frame: &mut Vec<u8>, v: Vec2) {
+    frame.extend(v.x.to_le_bytes());
+    frame.extend(v.y.to_le_bytes());
+}
+
+fn read_bytes<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(input)?))
+}
+
+fn read_f32(input: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_le_bytes(read_bytes(input)?))
+}
+
+fn read_vec2(input: &mut impl Read) -> io::Result<Vec2> {
+    Ok(vec2(read_f32(input)?, read_f32(input)?))
+}
+
+fn read_phase(input: &mut impl Read) -> io::Result<MarkerType> {
+    match read_bytes(input)? {
+        [0] => Ok(MarkerType::ToFood),
+        [1] => Ok(MarkerType::ToHome),
+        [phase] => Err(invalid(&format!("unknown phase {}", phase))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn recording(frames: &[Vec<SimEvent>]) -> (Colony, Vec<u8>) {
+        let world = World::new(800, 600);
+        let colony = Colony::new(vec2(100.0, 200.0), 3);
+        let mut out = vec![];
+        let mut recorder = Recorder::new(&mut out, &world, 0.016).unwrap();
+        for events in frames {
+            recorder.record(&colony, events).unwrap();
+        }
+        (colony, out)
+    }
+
+    fn open_err(bytes: Vec<u8>) -> io::Error {
+        Reader::new(Cursor::new(bytes))
+            .err()
+            .expect("open should fail")
+    }
+
+    #[test]
+    fn frames_read_back() {
+        let events = vec![
+            SimEvent::FoodAdded {
+                position: vec2(1.0, 2.0),
+                kind: FoodKind::Carcass,
+                quantity: 50.0,
+            },
+            SimEvent::FoodPicked {
+                ant: 2,
+                position: vec2(3.0, 4.0),
+                value: 1.5,
+            },
+            SimEvent::FoodDelivered { ant: 1, value: 1.5 },
+            SimEvent::MarkerAdded {
+                marker_type: MarkerType::ToHome,
+                position: vec2(5.0, 6.0),
+                intensity: 0.75,
+            },
+        ];
+        let (colony, bytes) = recording(&[events.clone(), vec![]]);
+
+        let mut reader = Reader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.time_step, 0.016);
+        assert_eq!((reader.width, reader.height), (800, 600));
+
+        let frame = reader.next_frame().unwrap().unwrap();
+        assert_eq!(frame.tick, 0);
+        assert_eq!(frame.events, events);
+        assert_eq!(frame.ants.len(), 3);
+        for (ant, (id, position, heading, phase)) in frame.ants.iter().zip(colony.ants().states()) {
+            assert_eq!(ant.id, id);
+            assert_eq!(ant.position, position);
+            assert_eq!(ant.phase, phase);
+            let error = (ant.heading - heading.rem_euclid(TAU)).abs();
+            assert!(error < 1e-3 || TAU - error < 1e-3);
+        }
+
+        let frame = reader.next_frame().unwrap().unwrap();
+        assert_eq!(frame.tick, 1);
+        assert!(frame.events.is_empty());
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn header_is_checked() {
+        let (_, mut bytes) = recording(&[]);
+        bytes[0] = b'X';
+        assert_eq!(open_err(bytes).to_string(), "not an ant recording");
+
+        let (_, mut bytes) = recording(&[]);
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(open_err(bytes).to_string(), "unsupported version 1");
+
+        let (_, bytes) = recording(&[]);
+        let truncated = open_err(bytes[..8].to_vec());
+        assert_eq!(truncated.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
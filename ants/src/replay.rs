@@ -0,0 +1,406 @@
+//! User inputs recorded with `--record-inputs <file>` and replayed with
+//! `--replay <file>`.
+//!
+//! Recordings are text files holding the random seed and the command line
+//! options of the run, one per `arg` line, followed by each input with the
+//! tick it was applied on:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! seed 1697712345
+//! arg --size
+//! arg 800x600
+//! 120 toggle energy             # tick toggle
+//! 240 wind                      # next wind mode
+//! 300 population 612            # ants
+//! 310 resize 1280 720           # width height
+//! 400 tool food 512.5 300.25 20 100 seed mud
+//! ```
+//!
+//! Tools are recorded with the palette they were applied with: tool, x, y,
+//! radius, quantity, food kind and terrain kind.
+
+use std::{
+    fmt::Debug,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+};
+
+use macroquad::prelude::{vec2, KeyCode, Vec2};
+
+use crate::{
+    schedule,
+    sim::{FoodKind, TerrainKind, World},
+    simulation::Simulation,
+    tools::{Palette, Tool},
+    NEST_MARGIN,
+};
+
+/// Options left out of recordings, they don't change the run
+const SKIPPED_ARGS: [&str; 4] = ["--seed", "--record", "--record-inputs", "--replay"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Toggle {
+    Grid,
+    Ants,
+    Markers,
+    Stats,
+    Trails,
+    DayNight,
+    Homing,
+    MergeMarkers,
+    Energy,
+    WallFollowing,
+    Crowding,
+    Levy,
+}
+
+impl Toggle {
+    pub const ALL: [Toggle; 12] = [
+        Toggle::Grid,
+        Toggle::Ants,
+        Toggle::Markers,
+        Toggle::Stats,
+        Toggle::Trails,
+        Toggle::DayNight,
+        Toggle::Homing,
+        Toggle::MergeMarkers,
+        Toggle::Energy,
+        Toggle::WallFollowing,
+        Toggle::Crowding,
+        Toggle::Levy,
+    ];
+
+    pub const KEYS: [KeyCode; 12] = [
+        KeyCode::G,
+        KeyCode::A,
+        KeyCode::M,
+        KeyCode::S,
+        KeyCode::P,
+        KeyCode::D,
+        KeyCode::H,
+        KeyCode::N,
+        KeyCode::E,
+        KeyCode::O,
+        KeyCode::C,
+        KeyCode::L,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Toggle::Grid => "grid",
+            Toggle::Ants => "ants",
+            Toggle::Markers => "markers",
+            Toggle::Stats => "stats",
+            Toggle::Trails => "trails",
+            Toggle::DayNight => "day_night",
+            Toggle::Homing => "homing",
+            Toggle::MergeMarkers => "merge_markers",
+            Toggle::Energy => "energy",
+            Toggle::WallFollowing => "wall_following",
+            Toggle::Crowding => "crowding",
+            Toggle::Levy => "levy",
+        }
+    }
+}
+
+/// A user action changing the run
+#[derive(Clone)]
+pub enum Input {
+    Toggle(Toggle),
+    Wind,
+    Population(usize),
+    Resize(usize, usize),
+    Tool { palette: Palette, position: Vec2 },
+}
+
+impl Input {
+    pub fn apply(&self, sim: &mut Simulation) {
+        match self {
+            Input::Toggle(toggle) => {
+                let cfg = &mut sim.cfg;
+                let value = match toggle {
+                    Toggle::Grid => &mut cfg.draw_grid,
+                    Toggle::Ants => &mut cfg.draw_ants,
+                    Toggle::Markers => &mut cfg.draw_markers,
+                    Toggle::Stats => &mut cfg.draw_stats,
+                    Toggle::Trails => &mut cfg.draw_trails,
+                    Toggle::DayNight => &mut cfg.day_night,
+                    Toggle::Homing => &mut cfg.path_integration,
+                    Toggle::MergeMarkers => &mut sim.world.merge_markers,
+                    Toggle::Energy => &mut cfg.energy,
+                    Toggle::WallFollowing => &mut cfg.wall_following,
+                    Toggle::Crowding => &mut cfg.crowding,
+                    Toggle::Levy => &mut cfg.levy_flight,
+                };
+                *value = !*value;
+            }
+            Input::Wind => sim.cfg.wind = sim.cfg.wind.next(),
+            Input::Population(count) => sim.colony.set_population(*count, &mut sim.world),
+            Input::Resize(width, height) => {
+                sim.world.resize(*width, *height);
+                // the nest marker may have been dropped with the cut off cells
+                let size = vec2(*width as f32, *height as f32);
+                let margin = Vec2::splat(NEST_MARGIN).min(size * 0.5);
+                let position = sim.colony.position.max(margin).min(size - margin);
+                sim.colony.move_to(position, &mut sim.world);
+            }
+            Input::Tool { palette, position } => {
+                palette.apply(*position, &mut sim.world, &mut sim.colony)
+            }
+        }
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Input::Toggle(toggle) => write!(out, "toggle {}", toggle.name()),
+            Input::Wind => write!(out, "wind"),
+            Input::Population(count) => write!(out, "population {}", count),
+            Input::Resize(width, height) => write!(out, "resize {} {}", width, height),
+            Input::Tool { palette, position } => write!(
+                out,
+                "tool {} {} {} {} {} {} {}",
+                palette.tool.name(),
+                position.x,
+                position.y,
+                palette.radius,
+                palette.quantity,
+                name(palette.food_kind),
+                name(palette.terrain_kind)
+            ),
+        }
+    }
+
+    fn parse(tokens: &[&str]) -> Result<Self, String> {
+        let token = |i: usize| tokens.get(i).copied().ok_or("missing argument");
+        let num = |i: usize| -> Result<f32, String> {
+            let token = token(i)?;
+            token
+                .parse()
+                .map_err(|_| format!("invalid number `{}`", token))
+        };
+        let count = |i: usize| -> Result<usize, String> {
+            let token = token(i)?;
+            token
+                .parse()
+                .map_err(|_| format!("invalid count `{}`", token))
+        };
+
+        Ok(match token(0)? {
+            "toggle" => Input::Toggle(find(&Toggle::ALL, Toggle::name, token(1)?)?),
+            "wind" => Input::Wind,
+            "population" => Input::Population(count(1)?),
+            "resize" => {
+                let (width, height) = (count(1)?, count(2)?);
+                World::check_size(width, height)?;
+                Input::Resize(width, height)
+            }
+            "tool" => Input::Tool {
+                palette: Palette {
+                    tool: find(&Tool::ALL, Tool::name, token(1)?)?,
+                    radius: num(4)?,
+                    quantity: num(5)?,
+                    food_kind: find(
+                        &[FoodKind::Seed, FoodKind::Fruit, FoodKind::Carcass],
+                        |kind| name(*kind),
+                        token(6)?,
+                    )?,
+                    terrain_kind: find(
+                        &[
+                            TerrainKind::Ground,
+                            TerrainKind::Grass,
+                            TerrainKind::Sand,
+                            TerrainKind::Mud,
+                            TerrainKind::Wall,
+                        ],
+                        |kind| name(*kind),
+                        token(7)?,
+                    )?,
+                },
+                position: vec2(num(2)?, num(3)?),
+            },
+            input => return Err(format!("unknown input `{}`", input)),
+        })
+    }
+}
+
+/// Lowercase name of an enum variant
+fn name(value: impl Debug) -> String {
+    format!("{:?}", value).to_lowercase()
+}
+
+fn find<T: Copy, N: AsRef<str>>(
+    values: &[T],
+    name: impl Fn(&T) -> N,
+    token: &str,
+) -> Result<T, String> {
+    values
+        .iter()
+        .find(|value| name(value).as_ref() == token)
+        .copied()
+        .ok_or_else(|| format!("unknown name `{}`", token))
+}
+
+/// Options of a run worth keeping in its recording
+pub fn recorded_args(args: &[String]) -> Vec<String> {
+    let mut recorded = vec![];
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if SKIPPED_ARGS.contains(&arg.as_str()) {
+            args.next();
+        } else {
+            recorded.push(arg.clone());
+        }
+    }
+    recorded
+}
+
+pub struct InputRecorder {
+    out: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn create(path: &str, seed: u64, args: &[String]) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "seed {}", seed)?;
+        for arg in args {
+            writeln!(out, "arg {}", arg)?;
+        }
+        out.flush()?;
+        Ok(Self { out })
+    }
+
+    /// Writes an input, flushing right away since inputs are rare
+    pub fn record(&mut self, tick: u32, input: &Input) -> io::Result<()> {
+        write!(self.out, "{} ", tick)?;
+        input.write(&mut self.out)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+/// Recorded run, giving back its inputs tick by tick
+pub struct Replay {
+    pub seed: u64,
+    pub args: Vec<String>,
+    inputs: Vec<(u32, Input)>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let src = fs::read_to_string(path)?;
+        Self::parse(&src).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut seed = None;
+        let mut args = vec![];
+        let header = |line: &str| {
+            // args are kept whole, they may hold spaces or `#`
+            if let Some(arg) = line.strip_prefix("arg ") {
+                args.push(arg.to_owned());
+                return Ok(true);
+            }
+            match schedule::tokens(line).as_slice() {
+                ["seed", value] => {
+                    let value = value
+                        .parse()
+                        .map_err(|_| format!("invalid seed `{}`", value))?;
+                    seed = Some(value);
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        };
+        let inputs = schedule::parse(src, "tick", header, Input::parse)?;
+
+        Ok(Self {
+            seed: seed.ok_or("missing seed")?,
+            args,
+            inputs,
+            next: 0,
+        })
+    }
+
+    /// Next input due on `tick`, if any
+    pub fn next(&mut self, tick: u32) -> Option<Input> {
+        let (at, input) = self.inputs.get(self.next)?;
+        if *at > tick {
+            return None;
+        }
+        self.next += 1;
+        Some(input.clone())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.inputs.len() - self.next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(src: &str) -> String {
+        Replay::parse(src).err().expect("parse should fail")
+    }
+
+    #[test]
+    fn written_inputs_parse_back() {
+        let inputs = [
+            Input::Toggle(Toggle::WallFollowing),
+            Input::Wind,
+            Input::Population(612),
+            Input::Resize(1280, 720),
+        ];
+        let mut src = "seed 42\narg --size\narg 800x600 # kept\n".to_owned();
+        for (tick, input) in inputs.iter().enumerate() {
+            let mut line = vec![];
+            input.write(&mut line).unwrap();
+            src += &format!("{} {}\n", tick, String::from_utf8(line).unwrap());
+        }
+
+        let mut replay = Replay::parse(&src).unwrap();
+        assert_eq!(replay.seed, 42);
+        assert_eq!(replay.args, ["--size", "800x600 # kept"]);
+        assert!(matches!(
+            replay.next(0),
+            Some(Input::Toggle(Toggle::WallFollowing))
+        ));
+        assert!(matches!(replay.next(1), Some(Input::Wind)));
+        assert!(matches!(replay.next(2), Some(Input::Population(612))));
+        assert!(matches!(replay.next(3), Some(Input::Resize(1280, 720))));
+        assert_eq!(replay.remaining(), 0);
+    }
+
+    #[test]
+    fn same_tick_inputs_keep_file_order() {
+        let src = "seed 1\n20 population 3\n10 population 1\n20 population 4\n10 wind\n";
+        let mut replay = Replay::parse(src).unwrap();
+        assert!(replay.next(9).is_none());
+        assert!(matches!(replay.next(10), Some(Input::Population(1))));
+        assert!(matches!(replay.next(10), Some(Input::Wind)));
+        assert!(replay.next(10).is_none());
+        assert!(matches!(replay.next(20), Some(Input::Population(3))));
+        assert!(matches!(replay.next(20), Some(Input::Population(4))));
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        assert_eq!(
+            parse_err("seed 1\n\n-5 wind\n"),
+            "line 3: invalid tick `-5`"
+        );
+        assert_eq!(parse_err("seed x\n"), "line 1: invalid seed `x`");
+        assert_eq!(
+            parse_err("seed 1\n# resize\n4 resize 0 600\n"),
+            "line 3: invalid size 0x600, sides go from 1 to 65535 pixels"
+        );
+        assert_eq!(
+            parse_err("seed 1\n4 toggle nothing\n"),
+            "line 2: unknown name `nothing`"
+        );
+        assert_eq!(parse_err("10 wind\n"), "missing seed");
+    }
+}
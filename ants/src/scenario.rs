@@ -0,0 +1,173 @@
+use std::{fs, io};
+
+use macroquad::prelude::{vec2, Vec2};
+
+use crate::{
+    schedule,
+    sim::{Colony, Food, FoodKind, TerrainKind, World},
+    Config,
+};
+
+pub enum Event {
+    AddFood {
+        position: Vec2,
+        kind: FoodKind,
+        quantity: f32,
+    },
+    RemoveFood {
+        position: Vec2,
+        radius: f32,
+    },
+    Wall {
+        min: Vec2,
+        max: Vec2,
+    },
+    MoveNest(Vec2),
+    Set {
+        name: String,
+        value: f32,
+    },
+}
+
+/// Events scheduled at given sim times, loaded from a text file where each
+/// line is `<time> <event> <args...>`:
+///
+/// ```text
+/// # comments and blank lines are ignored
+/// 0    food 900 360 seed 100      # x y kind quantity
+/// 120  remove_food 900 360 30     # x y radius
+/// 60   wall 500 200 520 500       # x1 y1 x2 y2
+/// 90   nest 300 300               # x y
+/// 30   set move_speed 80          # parameter value
+/// ```
+pub struct Scenario {
+    events: Vec<(f32, Event)>,
+    next: usize,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let src = fs::read_to_string(path)?;
+        Self::parse(&src).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let events = schedule::parse(src, "time", |_| Ok(false), parse_event)?;
+        Ok(Self { events, next: 0 })
+    }
+
+    /// Applies every event scheduled up to `time`
+    pub fn apply(&mut self, time: f32, world: &mut World, colony: &mut Colony, cfg: &mut Config) {
+        while let Some((at, event)) = self.events.get(self.next) {
+            if *at > time {
+                break;
+            }
+
+            match event {
+                Event::AddFood {
+                    position,
+                    kind,
+                    quantity,
+                } => world.add_food(Food::new(*position, *kind, *quantity)),
+                Event::RemoveFood { position, radius } => world.remove_food(*position, *radius),
+                Event::Wall { min, max } => world.terrain.paint_rect(*min, *max, TerrainKind::Wall),
+                Event::MoveNest(position) => colony.move_to(*position, world),
+                Event::Set { name, value } => {
+                    cfg.set_param(name, *value);
+                }
+            }
+            self.next += 1;
+        }
+    }
+}
+
+fn parse_event(tokens: &[&str]) -> Result<Event, String> {
+    let num = |i: usize| -> Result<f32, String> {
+        let token = tokens.get(i).ok_or("missing argument")?;
+        token
+            .parse()
+            .map_err(|_| format!("invalid number `{}`", token))
+    };
+    let name = tokens.first().ok_or("missing event")?;
+
+    let event = match *name {
+        "food" => Event::AddFood {
+            position: vec2(num(1)?, num(2)?),
+            kind: match tokens.get(3) {
+                Some(&"seed") => FoodKind::Seed,
+                Some(&"fruit") => FoodKind::Fruit,
+                Some(&"carcass") => FoodKind::Carcass,
+                Some(kind) => return Err(format!("unknown food kind `{}`", kind)),
+                None => return Err("missing food kind".to_owned()),
+            },
+            quantity: num(4)?,
+        },
+        "remove_food" => Event::RemoveFood {
+            position: vec2(num(1)?, num(2)?),
+            radius: num(3)?,
+        },
+        "wall" => Event::Wall {
+            min: vec2(num(1)?, num(2)?),
+            max: vec2(num(3)?, num(4)?),
+        },
+        "nest" => Event::MoveNest(vec2(num(1)?, num(2)?)),
+        "set" => {
+            let name = tokens.get(1).ok_or("missing parameter")?;
+            if !Config::PARAMS.contains(name) {
+                return Err(format!("unknown parameter `{}`", name));
+            }
+            Event::Set {
+                name: name.to_string(),
+                value: num(2)?,
+            }
+        }
+        _ => return Err(format!("unknown event `{}`", name)),
+    };
+
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(src: &str) -> String {
+        Scenario::parse(src).err().expect("parse should fail")
+    }
+
+    #[test]
+    fn same_time_events_keep_file_order() {
+        let src = "\
+            # comment\n\
+            30 nest 1 1\n\
+            0.5 set move_speed 80   # first\n\
+            30 remove_food 2 2 5\n\
+            0.5 wall 0 0 10 10\n";
+        let scenario = Scenario::parse(src).unwrap();
+        let times: Vec<f32> = scenario.events.iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, [0.5, 0.5, 30.0, 30.0]);
+        assert!(matches!(scenario.events[0].1, Event::Set { value, .. } if value == 80.0));
+        assert!(matches!(scenario.events[1].1, Event::Wall { .. }));
+        assert!(matches!(scenario.events[2].1, Event::MoveNest(_)));
+        assert!(matches!(scenario.events[3].1, Event::RemoveFood { radius, .. } if radius == 5.0));
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        assert_eq!(
+            parse_err("\n\nsoon nest 1 1"),
+            "line 3: invalid time `soon`"
+        );
+        assert_eq!(parse_err("NaN nest 1 1"), "line 1: invalid time `NaN`");
+        assert_eq!(
+            parse_err("0 food 1 1 rock 5"),
+            "line 1: unknown food kind `rock`"
+        );
+        assert_eq!(parse_err("0 wall 1 1 2"), "line 1: missing argument");
+        assert_eq!(
+            parse_err("0 set speed 2"),
+            "line 1: unknown parameter `speed`"
+        );
+        assert_eq!(parse_err("1\n"), "line 1: missing event");
+    }
+}
@@ -0,0 +1,54 @@
+//! Timed text files shared by scenarios and input replays, where each line is
+//! `<time> <entry> <args...>`. Everything after a `#` is a comment and blank
+//! lines are ignored.
+
+use std::{cmp::Ordering, str::FromStr};
+
+/// Tokens of a line, without its comment
+pub fn tokens(line: &str) -> Vec<&str> {
+    line.split('#')
+        .next()
+        .unwrap_or("")
+        .split_whitespace()
+        .collect()
+}
+
+/// Parses the timed lines of `src` with `entry`, called on the tokens after
+/// the time. `header` sees every raw line first and returns `true` for the
+/// ones it takes, they hold no time. Errors give the line number.
+///
+/// Entries are sorted by time, same-time entries in file order.
+pub fn parse<T, E>(
+    src: &str,
+    what: &str,
+    mut header: impl FnMut(&str) -> Result<bool, String>,
+    entry: impl Fn(&[&str]) -> Result<E, String>,
+) -> Result<Vec<(T, E)>, String>
+where
+    T: FromStr + PartialOrd,
+{
+    let mut entries = vec![];
+
+    for (i, line) in src.lines().enumerate() {
+        let line_error = |err: String| format!("line {}: {}", i + 1, err);
+        if header(line).map_err(line_error)? {
+            continue;
+        }
+
+        let tokens = tokens(line);
+        let Some((time, rest)) = tokens.split_first() else {
+            continue;
+        };
+        let invalid = || line_error(format!("invalid {} `{}`", what, time));
+        let time: T = time.parse().map_err(|_| invalid())?;
+        // NaN is not ordered with itself and could not be scheduled
+        if time.partial_cmp(&time).is_none() {
+            return Err(invalid());
+        }
+        entries.push((time, entry(rest).map_err(line_error)?));
+    }
+
+    // stable sort keeps same-time entries in file order
+    entries.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    Ok(entries)
+}
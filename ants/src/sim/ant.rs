@@ -1,244 +1,890 @@
-use std::f32::consts::PI;
+use std::f32::consts::{PI, TAU};
 
 use macroquad::{
-    prelude::{vec2, Vec2, RED},
+    models::{draw_mesh, Mesh, Vertex},
+    prelude::{vec2, vec3, Color, Vec2},
     rand,
-    texture::{draw_texture_ex, DrawTextureParams, Texture2D},
-    window::{screen_height, screen_width},
+    texture::Texture2D,
 };
 
 use crate::{
-    DIRECTION_NOISE, DIRECTION_UPDATE_PERIOD, MARKER_DETECTION_RADIUS, MARKER_PERIOD, MAX_RESERVE,
-    MOVE_SPEED, ROTATION_SPEED,
+    Config, ANT_STRIDE, DIRECTION_UPDATE_PERIOD, ENERGY_PER_FOOD, FOOD_COOLDOWN, LEVY_MAX_RUN,
+    MARKER_PERIOD, MAX_ENERGY, MAX_HANDLING_WAIT, MAX_RESERVE, ROTATION_SPEED,
 };
 
-use super::{Marker, MarkerType, World};
+use super::{AntGrid, FoodKind, Marker, MarkerType, SimEvent, TerrainKind, WindMode, World};
 
+/// Ants per mesh, keeps vertex indices within `u16` with room for a carried
+/// item each
+pub const ANT_BATCH_SIZE: usize = 8000;
+/// Walking frames side by side in the sprite sheet, followed by the carried
+/// food item
+const WALK_FRAMES: usize = 4;
+const SHEET_CELLS: f32 = WALK_FRAMES as f32 + 1.0;
+
+/// Food source an ant is standing at while picking an item
 #[derive(Clone, Copy)]
-pub struct Ant {
-    position: Vec2,
-    direction: Direction,
-    last_direction_update: f32,
-    last_marker: f32,
-    phase: MarkerType,
-    reserve: f32,
+struct Handling {
+    food: Vec2,
+    /// Group of carriers joined at the source
+    group: u32,
+    timer: f32,
+}
+
+/// All ants of a colony stored as a structure of arrays, so the movement
+/// passes run over contiguous `f32` slices the compiler can vectorize.
+#[derive(Default)]
+pub struct Ants {
+    /// Stable ids, indices change as ants are removed
+    id: Vec<u32>,
+    next_id: u32,
+    pos_x: Vec<f32>,
+    pos_y: Vec<f32>,
+    angle: Vec<f32>,
+    dir_x: Vec<f32>,
+    dir_y: Vec<f32>,
+    target_x: Vec<f32>,
+    target_y: Vec<f32>,
+    step_x: Vec<f32>,
+    step_y: Vec<f32>,
+    last_direction_update: Vec<f32>,
+    last_marker: Vec<f32>,
+    phase: Vec<MarkerType>,
+    reserve: Vec<f32>,
+    load: Vec<f32>,
+    handling: Vec<Option<Handling>>,
+    food_cooldown: Vec<f32>,
+    home_x: Vec<f32>,
+    home_y: Vec<f32>,
+    energy: Vec<f32>,
+    /// Side of the wall being followed, `1.0` or `-1.0` as a turn direction
+    wall_side: Vec<f32>,
+    /// Time left following a wall, steering by markers is suspended meanwhile
+    wall_timer: Vec<f32>,
+    /// Whether the side probe touched the wall being followed last tick
+    wall_contact: Vec<bool>,
+    /// Ants back at the nest rest there while the activity is below this,
+    /// drawn anew on each arrival
+    rest: Vec<f32>,
+    resting: Vec<bool>,
+    at_nest: Vec<bool>,
+    /// Distance left to walk straight in the current Lévy flight run
+    run_left: Vec<f32>,
+    /// Progress through the walking animation, from 0 to 1
+    gait: Vec<f32>,
+    /// Kind of the last food picked, drawn while carrying it
+    cargo: Vec<FoodKind>,
+    neighbours: AntGrid,
+    meshes: Vec<Mesh>,
 }
 
-impl Ant {
-    pub fn new(position: Vec2, rotation: f32) -> Self {
-        Self {
-            position,
-            direction: Direction::new(rotation, ROTATION_SPEED),
-            last_direction_update: rand::gen_range(0., 100.0) * 0.01 * DIRECTION_UPDATE_PERIOD,
-            last_marker: rand::gen_range(0., 100.0) * MARKER_PERIOD * 0.01,
-            phase: MarkerType::ToFood,
-            reserve: MAX_RESERVE,
+impl Ants {
+    pub fn len(&self) -> usize {
+        self.pos_x.len()
+    }
+
+    /// Id, position, heading and phase of every ant, hungry ants heading
+    /// home empty handed are still looking for food
+    pub fn states(&self) -> impl Iterator<Item = (u32, Vec2, f32, MarkerType)> + '_ {
+        (0..self.len()).map(|i| {
+            let phase = if self.load[i] > 0.0 {
+                MarkerType::ToHome
+            } else {
+                MarkerType::ToFood
+            };
+            (self.id[i], self.position(i), self.angle[i], phase)
+        })
+    }
+
+    /// Ants bringing food home, hungry ants heading back empty handed
+    /// don't count
+    pub fn carrying(&self) -> usize {
+        self.load.iter().filter(|load| **load > 0.0).count()
+    }
+
+    /// Adds an ant at `position`, `home` away from the nest
+    pub fn push(&mut self, position: Vec2, rotation: f32, home: Vec2) {
+        // spread the gaits without drawing random numbers
+        let gait = (self.len() as f32 * 0.618).fract();
+        let (sin, cos) = rotation.sin_cos();
+        self.id.push(self.next_id);
+        self.next_id += 1;
+        self.pos_x.push(position.x);
+        self.pos_y.push(position.y);
+        self.angle.push(rotation);
+        self.dir_x.push(cos);
+        self.dir_y.push(sin);
+        self.target_x.push(cos);
+        self.target_y.push(sin);
+        self.step_x.push(0.0);
+        self.step_y.push(0.0);
+        self.last_direction_update
+            .push(rand::gen_range(0., 100.0) * 0.01 * DIRECTION_UPDATE_PERIOD);
+        self.last_marker
+            .push(rand::gen_range(0., 100.0) * MARKER_PERIOD * 0.01);
+        self.phase.push(MarkerType::ToFood);
+        self.reserve.push(MAX_RESERVE);
+        self.load.push(0.0);
+        self.handling.push(None);
+        self.food_cooldown.push(0.0);
+        self.home_x.push(home.x);
+        self.home_y.push(home.y);
+        self.energy.push(MAX_ENERGY);
+        self.wall_side.push(1.0);
+        self.wall_contact.push(false);
+        self.wall_timer.push(0.0);
+        self.rest.push(0.0);
+        self.resting.push(false);
+        self.at_nest.push(false);
+        self.run_left.push(0.0);
+        self.gait.push(gait);
+        self.cargo.push(FoodKind::Seed);
+    }
+
+    /// Frees the spot held by the ant at a food source, if any
+    fn release_food(&self, i: usize, world: &mut World) {
+        let Some(handling) = self.handling[i] else {
+            return;
+        };
+
+        if let Some(food_spot) = world
+            .grid_food
+            .iter_at_mut(handling.food)
+            .find(|food_spot| food_spot.position == handling.food)
+        {
+            food_spot.release(handling.group);
         }
     }
 
-    pub fn update(&mut self, dt: f32, world: &mut World) {
-        self.update_position(dt);
+    /// Removes ants from the end until `len` remain
+    pub fn truncate(&mut self, len: usize, world: &mut World) {
+        for i in len..self.len() {
+            self.release_food(i, world);
+        }
 
-        match self.phase {
-            MarkerType::ToFood => self.check_food(world),
-            MarkerType::ToHome => {}
+        self.id.truncate(len);
+        self.pos_x.truncate(len);
+        self.pos_y.truncate(len);
+        self.angle.truncate(len);
+        self.dir_x.truncate(len);
+        self.dir_y.truncate(len);
+        self.target_x.truncate(len);
+        self.target_y.truncate(len);
+        self.step_x.truncate(len);
+        self.step_y.truncate(len);
+        self.last_direction_update.truncate(len);
+        self.last_marker.truncate(len);
+        self.phase.truncate(len);
+        self.reserve.truncate(len);
+        self.load.truncate(len);
+        self.handling.truncate(len);
+        self.food_cooldown.truncate(len);
+        self.home_x.truncate(len);
+        self.home_y.truncate(len);
+        self.energy.truncate(len);
+        self.wall_side.truncate(len);
+        self.wall_contact.truncate(len);
+        self.wall_timer.truncate(len);
+        self.rest.truncate(len);
+        self.resting.truncate(len);
+        self.at_nest.truncate(len);
+        self.run_left.truncate(len);
+        self.gait.truncate(len);
+        self.cargo.truncate(len);
+    }
+
+    /// Removes an ant, the last one takes its index
+    fn swap_remove(&mut self, i: usize, world: &mut World) {
+        self.release_food(i, world);
+
+        self.id.swap_remove(i);
+        self.pos_x.swap_remove(i);
+        self.pos_y.swap_remove(i);
+        self.angle.swap_remove(i);
+        self.dir_x.swap_remove(i);
+        self.dir_y.swap_remove(i);
+        self.target_x.swap_remove(i);
+        self.target_y.swap_remove(i);
+        self.step_x.swap_remove(i);
+        self.step_y.swap_remove(i);
+        self.last_direction_update.swap_remove(i);
+        self.last_marker.swap_remove(i);
+        self.phase.swap_remove(i);
+        self.reserve.swap_remove(i);
+        self.load.swap_remove(i);
+        self.handling.swap_remove(i);
+        self.food_cooldown.swap_remove(i);
+        self.home_x.swap_remove(i);
+        self.home_y.swap_remove(i);
+        self.energy.swap_remove(i);
+        self.wall_side.swap_remove(i);
+        self.wall_contact.swap_remove(i);
+        self.wall_timer.swap_remove(i);
+        self.rest.swap_remove(i);
+        self.resting.swap_remove(i);
+        self.at_nest.swap_remove(i);
+        self.run_left.swap_remove(i);
+        self.gait.swap_remove(i);
+        self.cargo.swap_remove(i);
+    }
+
+    /// Removes the ants that ran out of energy, returns how many died
+    pub fn remove_starved(&mut self, world: &mut World) -> usize {
+        let mut starved = 0;
+        for i in (0..self.len()).rev() {
+            if self.energy[i] <= 0.0 {
+                self.swap_remove(i, world);
+                starved += 1;
+            }
         }
+        starved
+    }
+
+    /// Ants waiting at a food source or resting at the nest stay in place
+    fn is_idle(&self, i: usize) -> bool {
+        self.handling[i].is_some() || self.resting[i]
+    }
+
+    /// Below `energy_return` of a full tank, searching is given up
+    fn is_hungry(&self, i: usize, cfg: &Config) -> bool {
+        cfg.energy && self.energy[i] < cfg.energy_return * MAX_ENERGY
+    }
+
+    fn position(&self, i: usize) -> Vec2 {
+        vec2(self.pos_x[i], self.pos_y[i])
+    }
 
-        self.last_direction_update += dt;
-        if self.last_direction_update > DIRECTION_UPDATE_PERIOD {
-            self.find_marker(world);
-            self.direction += rand::gen_range(-DIRECTION_NOISE, DIRECTION_NOISE);
-            self.last_direction_update = 0.0;
+    /// Turns the ant around instantly
+    fn reverse(&mut self, i: usize) {
+        self.target_x[i] = -self.target_x[i];
+        self.target_y[i] = -self.target_y[i];
+        self.dir_x[i] = self.target_x[i];
+        self.dir_y[i] = self.target_y[i];
+        self.angle[i] = self.target_y[i].atan2(self.target_x[i]);
+    }
+
+    /// Rotates the target heading, the ant turns towards it over time
+    fn turn(&mut self, i: usize, angle: f32) {
+        let (sin, cos) = angle.sin_cos();
+        let (x, y) = (self.target_x[i], self.target_y[i]);
+        self.target_x[i] = x * cos - y * sin;
+        self.target_y[i] = x * sin + y * cos;
+    }
+
+    fn set_target(&mut self, i: usize, dst: Vec2) {
+        let dst = dst.normalize();
+        self.target_x[i] = dst.x;
+        self.target_y[i] = dst.y;
+    }
+
+    pub fn update(&mut self, dt: f32, world: &mut World, cfg: &Config) {
+        // counted down even with wall following off, so ants turning it off
+        // midway get their steering back
+        for timer in &mut self.wall_timer {
+            *timer -= dt;
+        }
+        if cfg.wall_following {
+            self.avoid_walls(world, cfg);
+        }
+        self.update_steps(dt, world, cfg);
+        self.update_positions(world, cfg);
+        if cfg.energy {
+            self.spend_energy(cfg);
         }
 
-        self.last_marker += dt;
-        if self.last_marker > MARKER_PERIOD {
-            self.add_marker(world);
+        for i in 0..self.len() {
+            self.update_behaviour(i, dt, world, cfg);
         }
 
-        self.direction.update(dt);
+        self.update_directions(dt);
     }
 
-    fn add_marker(&mut self, world: &mut World) {
-        if self.reserve > 1.0 {
-            let marker_type = match self.phase {
-                MarkerType::ToFood => MarkerType::ToHome,
-                MarkerType::ToHome => MarkerType::ToFood,
+    /// Distance each ant walks this tick along its heading
+    fn update_steps(&mut self, dt: f32, world: &World, cfg: &Config) {
+        let n = self.len();
+
+        // terrain lookups are scattered, keep them out of the arithmetic pass
+        for i in 0..n {
+            self.step_x[i] = if self.is_idle(i) {
+                0.0
+            } else {
+                dt * cfg.move_speed * world.terrain.speed_at(self.position(i))
             };
+        }
 
-            world.add_marker(Marker::new(
-                self.position,
-                marker_type,
-                self.reserve * 0.02,
-                false,
-            ));
-            self.reserve *= 0.98;
+        let (step_x, step_y) = (&mut self.step_x[..n], &mut self.step_y[..n]);
+        let (dir_x, dir_y) = (&self.dir_x[..n], &self.dir_y[..n]);
+        for i in 0..n {
+            step_y[i] = step_x[i] * dir_y[i];
+            step_x[i] *= dir_x[i];
         }
 
-        self.last_marker = 0.0;
+        if cfg.wind != WindMode::Off {
+            for i in 0..n {
+                if !self.is_idle(i) {
+                    let drift = world.wind.at(self.position(i), cfg) * cfg.wind_ant_bias * dt;
+                    self.step_x[i] += drift.x;
+                    self.step_y[i] += drift.y;
+                }
+            }
+        }
+
+        if cfg.crowding {
+            self.neighbours.build(
+                &self.pos_x,
+                &self.pos_y,
+                world.width,
+                world.height,
+                cfg.separation_radius.max(1.0),
+            );
+            self.separate(dt, cfg);
+        }
     }
 
-    fn update_position(&mut self, dt: f32) {
-        self.position += (dt * MOVE_SPEED) * self.direction.vec;
+    /// Pushes ants away from neighbours closer than `separation_radius`
+    fn separate(&mut self, dt: f32, cfg: &Config) {
+        let radius = cfg.separation_radius;
+        for i in 0..self.len() {
+            if self.is_idle(i) {
+                continue;
+            }
+
+            let position = self.position(i);
+            let mut push = Vec2::ZERO;
+            self.neighbours.for_each_near(position, |j| {
+                let away = position - vec2(self.pos_x[j], self.pos_y[j]);
+                let distance = away.length();
+                if j != i && distance > 0.0 && distance < radius {
+                    push += away / distance * (1.0 - distance / radius);
+                }
+            });
 
-        // check out of bounds
-        if self.position.x < 0.0 {
-            self.position.x = 0.0;
-            self.direction.add_now(PI);
-        } else if self.position.x > screen_width() {
-            self.position.x = screen_width();
-            self.direction.add_now(PI);
+            self.step_x[i] += push.x * cfg.separation_strength * dt;
+            self.step_y[i] += push.y * cfg.separation_strength * dt;
         }
+    }
+
+    /// Ants wait instead of entering a cell already holding
+    /// `max_ants_per_cell` ants
+    fn limit_density(&mut self, cfg: &Config) {
+        let max = cfg.max_ants_per_cell as u32;
+        for i in 0..self.len() {
+            let position = self.position(i);
+            let from = self.neighbours.cell_of(position);
+            let to = self
+                .neighbours
+                .cell_of(position + vec2(self.step_x[i], self.step_y[i]));
+            if from == to {
+                continue;
+            }
 
-        if self.position.y < 0.0 {
-            self.position.y = 0.0;
-            self.direction.add_now(PI);
-        } else if self.position.y > screen_height() {
-            self.position.y = screen_height();
-            self.direction.add_now(PI);
+            if self.neighbours.counts[to] >= max {
+                self.step_x[i] = 0.0;
+                self.step_y[i] = 0.0;
+            } else {
+                self.neighbours.counts[to] += 1;
+                self.neighbours.counts[from] -= 1;
+            }
         }
     }
 
-    fn find_marker(&mut self, world: &mut World) {
-        let markers = world.get_grid(self.phase).get_all_at(self.position);
+    fn update_positions(&mut self, world: &World, cfg: &Config) {
+        let n = self.len();
+
+        // ants walking into a wall turn around instead of moving, ants caught
+        // inside a freshly placed wall may walk out of it
+        for i in 0..n {
+            let position = self.position(i);
+            let next = position + vec2(self.step_x[i], self.step_y[i]);
+            if world.terrain.get(next) == TerrainKind::Wall
+                && world.terrain.get(position) != TerrainKind::Wall
+            {
+                self.step_x[i] = 0.0;
+                self.step_y[i] = 0.0;
+                self.reverse(i);
+            }
+        }
+
+        if cfg.crowding && cfg.max_ants_per_cell > 0.0 {
+            self.limit_density(cfg);
+        }
+
+        let (pos_x, pos_y) = (&mut self.pos_x[..n], &mut self.pos_y[..n]);
+        let (step_x, step_y) = (&self.step_x[..n], &self.step_y[..n]);
+        for i in 0..n {
+            pos_x[i] += step_x[i];
+            pos_y[i] += step_y[i];
+        }
+
+        // the walking animation follows the distance covered
+        let gait = &mut self.gait[..n];
+        for i in 0..n {
+            gait[i] = (gait[i] + step_x[i].hypot(step_y[i]) / ANT_STRIDE).fract();
+        }
+
+        // check out of bounds, the steps keep the distance actually covered
+        let (width, height) = (world.width as f32, world.height as f32);
+        for i in 0..n {
+            let (x, y) = (self.pos_x[i], self.pos_y[i]);
+            self.pos_x[i] = x.clamp(0.0, width);
+            self.pos_y[i] = y.clamp(0.0, height);
+            if self.pos_x[i] != x || self.pos_y[i] != y {
+                self.step_x[i] += self.pos_x[i] - x;
+                self.step_y[i] += self.pos_y[i] - y;
+                self.reverse(i);
+            }
+        }
+
+        if cfg.path_integration {
+            self.integrate_paths(cfg);
+        }
+    }
 
-        let mut total_intensity = 0.0f32;
-        let mut point = Vec2::new(0.0, 0.0);
+    /// Probes `wall_probe_distance` ahead and steers clear of walls, then
+    /// keeps the wall on the same side for `wall_follow_time`, turning back
+    /// towards it once where its edge bends away. Following stops early when
+    /// the wall gets out of reach.
+    fn avoid_walls(&mut self, world: &World, cfg: &Config) {
+        // the world edges are followed like any other wall
+        let size = vec2(world.width as f32, world.height as f32);
+        let blocked = |pos: Vec2| {
+            pos.cmplt(Vec2::ZERO).any()
+                || pos.cmpgt(size).any()
+                || world.terrain.get(pos) == TerrainKind::Wall
+        };
 
-        let dir = self.direction.vec;
+        for i in 0..self.len() {
+            let position = self.position(i);
+            if blocked(position) {
+                // caught inside a freshly placed wall, let it walk out
+                continue;
+            }
 
-        for marker in markers.iter() {
-            let to_marker = marker.position - self.position;
-            let lenght = to_marker.length();
-            if lenght < MARKER_DETECTION_RADIUS {
-                if to_marker.dot(dir) > 0.0 {
-                    total_intensity += marker.intensity;
-                    point += marker.position * marker.intensity;
+            let dir = vec2(self.dir_x[i], self.dir_y[i]);
+            let heading = |angle: f32| Vec2::from_angle(angle).rotate(dir);
+            // samples along the whole probe so thin walls and corners are not skipped
+            let probe = |angle: f32| {
+                let ray = heading(angle) * cfg.wall_probe_distance;
+                (1..=4).any(|k| blocked(position + ray * k as f32 / 4.0))
+            };
+            let had_contact = self.wall_contact[i];
+            self.wall_contact[i] = false;
+
+            if probe(0.0) {
+                let side = if self.wall_timer[i] > 0.0 {
+                    self.wall_side[i]
+                } else {
+                    // turn towards where the ant wanted to go
+                    let target = vec2(self.target_x[i], self.target_y[i]);
+                    if dir.perp_dot(target) >= 0.0 {
+                        -1.0
+                    } else {
+                        1.0
+                    }
+                };
+
+                // a target right behind would never be turned to, reverse instead
+                match (1..8)
+                    .map(|k| -side * k as f32 * PI / 8.0)
+                    .find(|&angle| !probe(angle))
+                {
+                    Some(angle) => self.set_target(i, heading(angle)),
+                    None => self.reverse(i),
+                }
+                if self.wall_timer[i] <= 0.0 {
+                    self.wall_side[i] = side;
+                    self.wall_timer[i] = cfg.wall_follow_time;
+                }
+                self.wall_contact[i] = probe(side * PI / 2.0);
+            } else if self.wall_timer[i] > 0.0 {
+                let side = self.wall_side[i];
+                if probe(side * PI / 2.0) {
+                    self.wall_contact[i] = true;
+                } else if had_contact {
+                    // the edge bends away, turn once around the corner
+                    self.set_target(i, heading(side * PI / 2.0));
+                } else if !probe(side * 3.0 * PI / 4.0) {
+                    // the wall is out of reach, back to steering by markers
+                    self.wall_timer[i] = 0.0;
                 }
             }
         }
+    }
 
-        if total_intensity > 0.0 {
-            let dst = point / total_intensity - self.position;
-            let angle = (dst.x / dst.length()).acos();
+    /// Walking burns `energy_cost` per pixel
+    fn spend_energy(&mut self, cfg: &Config) {
+        let n = self.len();
+        let energy = &mut self.energy[..n];
+        let (step_x, step_y) = (&self.step_x[..n], &self.step_y[..n]);
+        for i in 0..n {
+            energy[i] -= (step_x[i] * step_x[i] + step_y[i] * step_y[i]).sqrt() * cfg.energy_cost;
+        }
+    }
+
+    /// Keeps the home vectors pointing to a nest moved by `offset`
+    pub fn move_home(&mut self, offset: Vec2) {
+        for i in 0..self.len() {
+            self.home_x[i] -= offset.x;
+            self.home_y[i] -= offset.y;
+        }
+    }
+
+    /// Dead reckoning of the offset from the nest, each step adds an error
+    /// proportional to the distance walked.
+    fn integrate_paths(&mut self, cfg: &Config) {
+        for i in 0..self.len() {
+            let step = vec2(self.step_x[i], self.step_y[i]);
+            let noise = vec2(rand::gen_range(-1.0, 1.0), rand::gen_range(-1.0, 1.0));
+            let error = noise * step.length() * cfg.path_integration_noise;
+            self.home_x[i] += step.x + error.x;
+            self.home_y[i] += step.y + error.y;
+        }
+    }
+
+    /// Turns every ant towards its target heading
+    fn update_directions(&mut self, dt: f32) {
+        let n = self.len();
+        let angle = &mut self.angle[..n];
+        let (dir_x, dir_y) = (&mut self.dir_x[..n], &mut self.dir_y[..n]);
+        let (target_x, target_y) = (&self.target_x[..n], &self.target_y[..n]);
+
+        for i in 0..n {
+            // dot product of the target with the heading normal
+            let delta = target_y[i] * dir_x[i] - target_x[i] * dir_y[i];
+            angle[i] += ROTATION_SPEED * delta * dt;
+        }
 
-            self.direction.target_angle = if dst.y > 0.0 { angle } else { -angle };
-            self.direction.update_target_vec();
+        for i in 0..n {
+            dir_x[i] = angle[i].cos();
+            dir_y[i] = angle[i].sin();
         }
     }
 
-    fn check_food(&mut self, world: &mut World) {
-        let mut food_spots = world.grid_food.get_all_at_mut(self.position);
-        for food_spot in food_spots.iter_mut() {
-            if self.position.distance(food_spot.position) < food_spot.radius {
-                self.phase = MarkerType::ToHome;
-                self.reserve = MAX_RESERVE;
-                self.direction.add_now(PI);
-                food_spot.pick();
-                return;
+    fn update_behaviour(&mut self, i: usize, dt: f32, world: &mut World, cfg: &Config) {
+        if self.resting[i] {
+            return;
+        }
+
+        if self.handling[i].is_some() {
+            self.handle_food(i, dt, world);
+            return;
+        }
+
+        self.food_cooldown[i] -= dt;
+
+        if self.phase[i] == MarkerType::ToFood && self.is_hungry(i, cfg) {
+            // too hungry to keep searching, head home empty handed
+            self.phase[i] = MarkerType::ToHome;
+            self.load[i] = 0.0;
+            self.reverse(i);
+        }
+
+        match self.phase[i] {
+            MarkerType::ToFood => self.check_food(i, world),
+            MarkerType::ToHome => {}
+        }
+
+        if cfg.levy_flight {
+            self.run_left[i] -= vec2(self.step_x[i], self.step_y[i]).length();
+        }
+
+        self.last_direction_update[i] += dt;
+        if self.last_direction_update[i] > cfg.direction_update_period && self.wall_timer[i] <= 0.0
+        {
+            let steered = self.steer(i, world, cfg);
+            if cfg.levy_flight && self.phase[i] == MarkerType::ToFood && !steered {
+                self.levy_flight(i, cfg);
+            } else {
+                // ants wander more in the dark
+                let noise = cfg.direction_noise * world.day.scale(cfg.night_noise, cfg);
+                self.turn(i, rand::gen_range(-noise, noise));
+                // searching starts with a new run once off the trail
+                self.run_left[i] = 0.0;
             }
+            self.last_direction_update[i] = 0.0;
+        }
+
+        self.last_marker[i] += dt;
+        if self.last_marker[i] > cfg.marker_period {
+            self.add_marker(i, world);
         }
     }
 
-    pub fn check_colony(&mut self, colony_position: Vec2) {
-        if self.position.distance(colony_position) < 10.0 {
-            match self.phase {
-                MarkerType::ToFood => {}
-                MarkerType::ToHome => {
-                    self.direction.add_now(PI);
-                    self.phase = MarkerType::ToFood;
-                }
+    fn add_marker(&mut self, i: usize, world: &mut World) {
+        let reserve = self.reserve[i];
+        if reserve > 1.0 {
+            let marker_type = match self.phase[i] {
+                MarkerType::ToFood => MarkerType::ToHome,
+                MarkerType::ToHome => MarkerType::ToFood,
+            };
+
+            // trails leading to richer food are laid stronger
+            let intensity = match self.phase[i] {
+                MarkerType::ToFood => reserve * 0.02,
+                MarkerType::ToHome => reserve * 0.02 * self.load[i],
+            };
+
+            // ants going home hungry have no food to point to
+            if intensity > 0.0 {
+                world.add_marker(Marker::new(self.position(i), marker_type, intensity, false));
             }
-            self.reserve = MAX_RESERVE;
-        }
-    }
-
-    pub fn draw(&self, texture: Texture2D) {
-        /*draw_circle(
-            self.position.x,
-            self.position.y,
-            MARKER_DETECTION_RADIUS,
-            Color::new(0.99, 0.98, 0.00, 0.1),
-        );*/
-        draw_texture_ex(
-            texture,
-            self.position.x - 5.0,
-            self.position.y - 7.5,
-            RED,
-            DrawTextureParams {
-                dest_size: Some(vec2(5.0, 7.0)),
-                source: None,
-                rotation: self.direction.angle + (PI / 2.0),
-                flip_x: false,
-                flip_y: false,
-                pivot: None,
-            },
-        );
+            self.reserve[i] *= 0.98;
+        }
+
+        self.last_marker[i] = 0.0;
     }
-}
 
-#[derive(Clone, Copy)]
-struct Direction {
-    angle: f32,
-    target_angle: f32,
-    rotation_speed: f32,
-    vec: Vec2,
-    target_vec: Vec2,
-}
+    /// Returns `true` when a marker or the home vector set the heading
+    fn steer(&mut self, i: usize, world: &World, cfg: &Config) -> bool {
+        let mut target = self.find_marker(i, world, cfg);
+
+        let home_vector = vec2(self.home_x[i], self.home_y[i]);
+        if cfg.path_integration
+            && matches!(self.phase[i], MarkerType::ToHome)
+            && home_vector.length() > 0.0
+        {
+            let home = -home_vector.normalize();
+            target = Some(match target {
+                Some(dst) => dst.normalize().lerp(home, cfg.path_integration_weight),
+                None => home,
+            });
+        }
 
-impl std::ops::AddAssign<f32> for Direction {
-    fn add_assign(&mut self, rhs: f32) {
-        self.target_angle += rhs;
-        self.update_target_vec();
+        match target.filter(|dst| dst.length() > 0.0) {
+            Some(dst) => {
+                self.set_target(i, dst);
+                true
+            }
+            None => false,
+        }
     }
-}
 
-impl std::ops::Add<f32> for Direction {
-    type Output = Self;
+    /// Walks straight until the run is over, then heads in a random
+    /// direction for a new run drawn from a power law of `levy_exponent`
+    fn levy_flight(&mut self, i: usize, cfg: &Config) {
+        if self.run_left[i] > 0.0 {
+            return;
+        }
 
-    fn add(mut self, rhs: f32) -> Self::Output {
-        self.target_angle += rhs;
-        self.update_target_vec();
-        self
+        self.set_target(i, Vec2::from_angle(rand::gen_range(0.0, TAU)));
+        // inverse transform sampling of a Pareto distribution
+        let u = rand::gen_range(f32::EPSILON, 1.0);
+        let run = cfg.levy_min_run * u.powf(-1.0 / (cfg.levy_exponent - 1.0).max(0.01));
+        self.run_left[i] = run.min(LEVY_MAX_RUN);
     }
-}
 
-impl Direction {
-    fn new(angle: f32, rotation_speed: f32) -> Self {
-        let mut dir = Self {
-            angle,
-            target_angle: angle,
-            rotation_speed,
-            vec: vec2(angle.cos(), angle.sin()),
-            target_vec: vec2(angle.cos(), angle.sin()),
-        };
+    /// Direction towards the weighted center of the markers ahead, if any
+    fn find_marker(&self, i: usize, world: &World, cfg: &Config) -> Option<Vec2> {
+        let position = self.position(i);
+        let dir = vec2(self.dir_x[i], self.dir_y[i]);
+
+        let (total_intensity, point) =
+            world
+                .get_field(self.phase[i])
+                .sense(position, dir, cfg.marker_detection_radius);
+
+        if total_intensity > 0.0 {
+            Some(point / total_intensity - position)
+        } else {
+            None
+        }
+    }
+
+    fn check_food(&mut self, i: usize, world: &mut World) {
+        if self.food_cooldown[i] > 0.0 {
+            return;
+        }
 
-        dir.update_vec();
-        dir.target_vec = dir.vec;
-        dir
+        let position = self.position(i);
+        let time = world.time;
+        let mut discovery = None;
+        for food_spot in world.grid_food.iter_at_mut(position) {
+            if food_spot.has_items() && position.distance(food_spot.position) < food_spot.radius {
+                discovery = food_spot
+                    .discover(time)
+                    .map(|delay| (food_spot.position, delay));
+                self.handling[i] = Some(Handling {
+                    food: food_spot.position,
+                    group: food_spot.grab(),
+                    timer: 0.0,
+                });
+                break;
+            }
+        }
+
+        if let Some((position, delay)) = discovery {
+            world.record_discovery(position, delay);
+        }
     }
 
-    fn update(&mut self, dt: f32) {
-        self.update_vec();
+    fn handle_food(&mut self, i: usize, dt: f32, world: &mut World) {
+        let Some(mut handling) = self.handling[i].take() else {
+            return;
+        };
+        handling.timer += dt;
+
+        let Some(food_spot) = world
+            .grid_food
+            .iter_at_mut(handling.food)
+            .find(|food_spot| food_spot.position == handling.food)
+        else {
+            // source depleted while waiting
+            return;
+        };
 
-        //let dir_nrm = self.vec.normalize();
-        let dir_nrm = vec2(-self.vec.y, self.vec.x);
-        let dir_delta = self.target_vec.dot(dir_nrm);
-        self.angle += self.rotation_speed * dir_delta * dt;
+        let handling_time = food_spot.kind.handling_time();
+        if handling.timer < handling_time {
+            self.handling[i] = Some(handling);
+            return;
+        }
+
+        if let Some(value) = food_spot.pick(handling.group) {
+            self.phase[i] = MarkerType::ToHome;
+            self.reserve[i] = MAX_RESERVE;
+            self.load[i] = value;
+            self.cargo[i] = food_spot.kind;
+            self.reverse(i);
+            world.log(SimEvent::FoodPicked {
+                ant: self.id[i],
+                position: handling.food,
+                value,
+            });
+        } else if handling.timer > handling_time + MAX_HANDLING_WAIT {
+            // not enough ants showed up to lift the item
+            food_spot.release(handling.group);
+            self.food_cooldown[i] = FOOD_COOLDOWN;
+            self.reverse(i);
+        } else {
+            self.handling[i] = Some(handling);
+        }
+    }
+
+    /// Returns the food value delivered to the colony
+    pub fn check_colony(&mut self, colony_position: Vec2, world: &mut World, cfg: &Config) -> f32 {
+        let activity = world.day.scale(cfg.night_activity, cfg);
+        let mut delivered = 0.0;
+        for i in 0..self.len() {
+            self.resting[i] = false;
+            let arrived = !self.at_nest[i];
+            self.at_nest[i] = self.position(i).distance(colony_position) < 10.0;
+            if self.at_nest[i] {
+                let mut waiting = false;
+                // searching ants coming by may stay in for the night too
+                if arrived && cfg.day_night {
+                    self.rest[i] = rand::gen_range(0.0, 1.0);
+                }
+                match self.phase[i] {
+                    MarkerType::ToFood => {}
+                    // back empty handed, it waits in the nest until the store
+                    // can feed it
+                    MarkerType::ToHome if self.load[i] <= 0.0 && self.is_hungry(i, cfg) => {
+                        waiting = true;
+                    }
+                    MarkerType::ToHome => {
+                        self.reverse(i);
+                        self.phase[i] = MarkerType::ToFood;
+                        if self.load[i] > 0.0 {
+                            delivered += self.load[i];
+                            world.log(SimEvent::FoodDelivered {
+                                ant: self.id[i],
+                                value: self.load[i],
+                            });
+                        }
+                        self.load[i] = 0.0;
+                    }
+                }
+                self.resting[i] = waiting || activity < self.rest[i];
+                self.reserve[i] = MAX_RESERVE;
+                self.home_x[i] = 0.0;
+                self.home_y[i] = 0.0;
+            }
+        }
+        delivered
     }
 
-    fn update_vec(&mut self) {
-        self.vec = vec2(self.angle.cos(), self.angle.sin());
+    /// Ants at the nest eat from the colony store until full
+    pub fn eat(&mut self, colony_position: Vec2, food: &mut f32) {
+        for i in 0..self.len() {
+            if self.energy[i] < MAX_ENERGY && self.position(i).distance(colony_position) < 10.0 {
+                let meal = ((MAX_ENERGY - self.energy[i]) / ENERGY_PER_FOOD).min(*food);
+                self.energy[i] += meal * ENERGY_PER_FOOD;
+                *food -= meal;
+            }
+        }
     }
 
-    fn update_target_vec(&mut self) {
-        self.target_vec = vec2(self.target_angle.cos(), self.target_angle.sin());
+    /// Draws the ants tinted with `color`, each carried food item in the
+    /// color of its kind, batched into as few meshes as the `u16` vertex
+    /// indices allow
+    pub fn draw(&mut self, texture: Texture2D, color: Color) {
+        let chunks = self.len().div_ceil(ANT_BATCH_SIZE);
+        self.meshes.resize_with(chunks, || Mesh {
+            vertices: vec![],
+            indices: vec![],
+            texture: Some(texture),
+        });
+
+        for (chunk, mesh) in self.meshes.iter_mut().enumerate() {
+            let start = chunk * ANT_BATCH_SIZE;
+            let end = (start + ANT_BATCH_SIZE).min(self.pos_x.len());
+            mesh.vertices.clear();
+            mesh.indices.clear();
+
+            for i in start..end {
+                let center = vec2(self.pos_x[i], self.pos_y[i]);
+                let heading = vec2(self.dir_x[i], self.dir_y[i]);
+                let frame = (self.gait[i] * WALK_FRAMES as f32) as usize % WALK_FRAMES;
+                push_sprite(
+                    mesh,
+                    center,
+                    heading * 3.5,
+                    heading.perp() * 2.5,
+                    frame,
+                    color,
+                );
+
+                if self.phase[i] == MarkerType::ToHome && self.load[i] > 0.0 {
+                    // held in the mandibles, just ahead of the head
+                    push_sprite(
+                        mesh,
+                        center + heading * 3.5,
+                        heading * 1.2,
+                        heading.perp() * 1.0,
+                        WALK_FRAMES,
+                        self.cargo[i].color(),
+                    );
+                }
+            }
+
+            draw_mesh(mesh);
+        }
     }
+}
 
-    fn add_now(&mut self, angle: f32) {
-        self.target_angle += angle;
-        self.update_target_vec();
-        self.angle = self.target_angle;
-        self.update_vec();
+/// Adds a quad showing `cell` of the sprite sheet, its top following
+/// `forward`
+fn push_sprite(
+    mesh: &mut Mesh,
+    center: Vec2,
+    forward: Vec2,
+    side: Vec2,
+    cell: usize,
+    color: Color,
+) {
+    let (u0, u1) = (cell as f32 / SHEET_CELLS, (cell + 1) as f32 / SHEET_CELLS);
+    let base = mesh.vertices.len() as u16;
+    for (corner, uv) in [
+        (center + forward - side, vec2(u0, 0.)),
+        (center + forward + side, vec2(u1, 0.)),
+        (center - forward + side, vec2(u1, 1.)),
+        (center - forward - side, vec2(u0, 1.)),
+    ] {
+        mesh.vertices.push(Vertex {
+            position: vec3(corner.x, corner.y, 0.),
+            uv,
+            color,
+        });
     }
+    mesh.indices
+        .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
 }
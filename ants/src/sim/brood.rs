@@ -0,0 +1,54 @@
+use crate::{Config, EGG_COST, LARVA_FEEDING_RATE};
+
+/// Lays eggs at the nest while the colony can pay for them
+#[derive(Default)]
+pub struct Queen {
+    timer: f32,
+}
+
+impl Queen {
+    /// Returns `true` when an egg is laid, taking its cost from `food`
+    pub fn lay(&mut self, dt: f32, food: &mut f32, cfg: &Config) -> bool {
+        self.timer = (self.timer + cfg.egg_laying_rate * dt).min(1.0);
+        if self.timer >= 1.0 && *food >= EGG_COST {
+            self.timer -= 1.0;
+            *food -= EGG_COST;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Brood {
+    /// Hatches into a larva after `egg_duration` seconds
+    Egg { age: f32 },
+    /// Grows into a worker once fed `larva_food` from the colony store
+    Larva { fed: f32 },
+}
+
+impl Brood {
+    pub fn update(&mut self, dt: f32, food: &mut f32, cfg: &Config) {
+        match self {
+            Brood::Egg { age } => {
+                *age += dt;
+                if *age >= cfg.egg_duration {
+                    *self = Brood::Larva { fed: 0.0 };
+                }
+            }
+            Brood::Larva { fed } => {
+                let meal = (LARVA_FEEDING_RATE * dt)
+                    .min(cfg.larva_food - *fed)
+                    .min(*food)
+                    .max(0.0);
+                *fed += meal;
+                *food -= meal;
+            }
+        }
+    }
+
+    pub fn is_grown(&self, cfg: &Config) -> bool {
+        matches!(self, Brood::Larva { fed } if *fed >= cfg.larva_food)
+    }
+}
@@ -1,46 +1,149 @@
 use std::f32::consts::PI;
 
 use macroquad::{
-    prelude::{Vec2, DARKBLUE},
+    prelude::{vec2, Color, Vec2, BEIGE, DARKBLUE, WHITE},
     rand,
     shapes::draw_circle,
     texture::Texture2D,
 };
 
-use crate::{Config, MAX_ANTS};
+use crate::{Config, ANT_COLOR};
 
-use super::{Ant, World};
+use super::{Ants, Brood, Marker, MarkerType, Queen, World};
 
 pub struct Colony {
     pub position: Vec2,
-    ants: [Ant; MAX_ANTS],
+    /// Food stored in the nest
+    pub food: f32,
+    /// Total food brought back since the start
+    pub delivered: f32,
+    /// Ants that ran out of energy
+    pub starved: usize,
+    /// Tint of the ants
+    pub color: Color,
+    ants: Ants,
+    queen: Queen,
+    brood: Vec<Brood>,
 }
 
 impl Colony {
-    pub fn new(position: Vec2) -> Self {
-        let mut ants = [Ant::new(position, 0.0); MAX_ANTS];
-        for ant in ants.iter_mut() {
-            *ant = Ant::new(position, rand::gen_range(2.0 * -PI, 2.0 * PI));
+    pub fn new(position: Vec2, count: usize) -> Self {
+        let mut colony = Self {
+            position,
+            food: 0.0,
+            delivered: 0.0,
+            starved: 0,
+            color: ANT_COLOR,
+            ants: Ants::default(),
+            queen: Queen::default(),
+            brood: vec![],
+        };
+        colony.spawn(position, count);
+        colony
+    }
+
+    /// Adds `count` ants heading in random directions from `position`
+    pub fn spawn(&mut self, position: Vec2, count: usize) {
+        for _ in 0..count {
+            self.ants.push(
+                position,
+                rand::gen_range(2.0 * -PI, 2.0 * PI),
+                position - self.position,
+            );
+        }
+    }
+
+    pub fn population(&self) -> usize {
+        self.ants.len()
+    }
+
+    /// Spawns ants at the nest or removes the most recent ones until the
+    /// colony holds `count` ants
+    pub fn set_population(&mut self, count: usize, world: &mut World) {
+        let population = self.population();
+        if count > population {
+            self.spawn(self.position, count - population);
+        } else {
+            self.ants.truncate(count, world);
+        }
+    }
+
+    pub fn ants(&self) -> &Ants {
+        &self.ants
+    }
+
+    pub fn eggs(&self) -> usize {
+        self.brood
+            .iter()
+            .filter(|brood| matches!(brood, Brood::Egg { .. }))
+            .count()
+    }
+
+    pub fn larvae(&self) -> usize {
+        self.brood.len() - self.eggs()
+    }
+
+    /// Number of ants on their way home with food
+    pub fn carrying(&self) -> usize {
+        self.ants.carrying()
+    }
+
+    /// Moves the nest and its permanent home marker, shifting the home
+    /// vectors of the ants to match
+    pub fn move_to(&mut self, position: Vec2, world: &mut World) {
+        world.remove_markers(MarkerType::ToHome, self.position, 1.0);
+        self.ants.move_home(position - self.position);
+        self.position = position;
+        world.add_marker(Marker::new(position, MarkerType::ToHome, 100.0, true));
+    }
+
+    pub fn update(&mut self, dt: f32, world: &mut World, cfg: &Config) {
+        self.ants.update(dt, world, cfg);
+        let delivered = self.ants.check_colony(self.position, world, cfg);
+        self.food += delivered;
+        self.delivered += delivered;
+        if cfg.energy {
+            self.ants.eat(self.position, &mut self.food);
         }
-        Self { position, ants }
+        self.starved += self.ants.remove_starved(world);
+        self.update_brood(dt, cfg);
     }
 
-    pub fn update(&mut self, dt: f32, world: &mut World) {
-        for ant in self.ants.iter_mut() {
-            ant.update(dt, world);
+    /// Lays eggs and feeds larvae from the store, growing larvae that had
+    /// enough into new workers at the nest
+    fn update_brood(&mut self, dt: f32, cfg: &Config) {
+        if self.queen.lay(dt, &mut self.food, cfg) {
+            self.brood.push(Brood::Egg { age: 0.0 });
         }
 
-        for ant in self.ants.iter_mut() {
-            ant.check_colony(self.position);
+        for brood in &mut self.brood {
+            brood.update(dt, &mut self.food, cfg);
         }
+
+        let count = self.brood.len();
+        self.brood.retain(|brood| !brood.is_grown(cfg));
+        self.spawn(self.position, count - self.brood.len());
     }
 
-    pub fn draw(&self, texture: Texture2D, cfg: &Config) {
+    pub fn draw(&mut self, texture: Texture2D, cfg: &Config) {
         draw_circle(self.position.x, self.position.y, 10.0, DARKBLUE);
+        for (i, brood) in self.brood.iter().enumerate() {
+            // spiral around the nest, oldest closest
+            let angle = i as f32 * 2.4;
+            let offset = vec2(angle.cos(), angle.sin()) * (12.0 + (i as f32).sqrt() * 2.0);
+            let (radius, color) = match brood {
+                Brood::Egg { .. } => (1.0, WHITE),
+                Brood::Larva { .. } => (1.5, BEIGE),
+            };
+            draw_circle(
+                self.position.x + offset.x,
+                self.position.y + offset.y,
+                radius,
+                color,
+            );
+        }
         if cfg.draw_ants {
-            for ant in self.ants.iter() {
-                ant.draw(texture);
-            }
+            self.ants.draw(texture, self.color);
         }
     }
 }
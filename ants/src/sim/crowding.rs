@@ -0,0 +1,68 @@
+use macroquad::prelude::Vec2;
+
+/// Spatial hash over ant positions, rebuilt every tick with a counting sort
+/// so neighbour queries only visit the surrounding cells.
+#[derive(Default)]
+pub struct AntGrid {
+    cell_size: f32,
+    width: usize,
+    height: usize,
+    /// Ants per cell, kept up to date as ants move between cells
+    pub counts: Vec<u32>,
+    start: Vec<usize>,
+    indices: Vec<usize>,
+}
+
+impl AntGrid {
+    pub fn cell_of(&self, pos: Vec2) -> usize {
+        let x = ((pos.x.max(0.0) / self.cell_size) as usize).min(self.width - 1);
+        let y = ((pos.y.max(0.0) / self.cell_size) as usize).min(self.height - 1);
+        y * self.width + x
+    }
+
+    pub fn build(&mut self, xs: &[f32], ys: &[f32], width: usize, height: usize, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.width = (width as f32 / cell_size) as usize + 1;
+        self.height = (height as f32 / cell_size) as usize + 1;
+
+        let cells = self.width * self.height;
+        self.counts.clear();
+        self.counts.resize(cells, 0);
+        for (x, y) in xs.iter().zip(ys) {
+            let cell = self.cell_of(Vec2::new(*x, *y));
+            self.counts[cell] += 1;
+        }
+
+        self.start.clear();
+        self.start.reserve(cells + 1);
+        let mut total = 0;
+        for count in &self.counts {
+            self.start.push(total);
+            total += *count as usize;
+        }
+        self.start.push(total);
+
+        let mut next = self.start.clone();
+        self.indices.resize(xs.len(), 0);
+        for (i, (x, y)) in xs.iter().zip(ys).enumerate() {
+            let cell = self.cell_of(Vec2::new(*x, *y));
+            self.indices[next[cell]] = i;
+            next[cell] += 1;
+        }
+    }
+
+    /// Calls `f` with the index of every ant in the 3x3 cells around `pos`
+    pub fn for_each_near(&self, pos: Vec2, mut f: impl FnMut(usize)) {
+        let cell = self.cell_of(pos);
+        let (cx, cy) = (cell % self.width, cell / self.width);
+
+        for y in cy.saturating_sub(1)..=(cy + 1).min(self.height - 1) {
+            for x in cx.saturating_sub(1)..=(cx + 1).min(self.width - 1) {
+                let cell = y * self.width + x;
+                for &i in &self.indices[self.start[cell]..self.start[cell + 1]] {
+                    f(i);
+                }
+            }
+        }
+    }
+}
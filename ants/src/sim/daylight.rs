@@ -0,0 +1,53 @@
+use std::f32::consts::TAU;
+
+use macroquad::{
+    prelude::{Color, Vec2},
+    shapes::draw_rectangle,
+};
+
+use crate::{Config, NIGHT_DARKNESS};
+
+/// Day clock, starting at noon and lasting `day_length` seconds per day
+#[derive(Default)]
+pub struct DayCycle {
+    time: f32,
+}
+
+impl DayCycle {
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
+
+    /// Hour of the day, from 0 to 24
+    pub fn hour(&self, cfg: &Config) -> f32 {
+        (12.0 + self.time / cfg.day_length * 24.0) % 24.0
+    }
+
+    /// Daylight from 0 at midnight to 1 at noon, always 1 without the cycle
+    pub fn light(&self, cfg: &Config) -> f32 {
+        if cfg.day_night {
+            0.5 + 0.5 * (self.time / cfg.day_length * TAU).cos()
+        } else {
+            1.0
+        }
+    }
+
+    /// Blends a night time factor into its day time value of 1
+    pub fn scale(&self, night: f32, cfg: &Config) -> f32 {
+        night + (1.0 - night) * self.light(cfg)
+    }
+
+    /// Darkens the world at night
+    pub fn draw(&self, size: Vec2, cfg: &Config) {
+        let darkness = (1.0 - self.light(cfg)) * NIGHT_DARKNESS;
+        if darkness > 0.0 {
+            draw_rectangle(
+                0.0,
+                0.0,
+                size.x,
+                size.y,
+                Color::new(0.0, 0.0, 0.1, darkness),
+            );
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use macroquad::prelude::Vec2;
+
+use super::{FoodKind, MarkerType};
+
+/// Something that happened during a step, logged by the world while
+/// recording
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimEvent {
+    FoodAdded {
+        position: Vec2,
+        kind: FoodKind,
+        quantity: f32,
+    },
+    FoodPicked {
+        ant: u32,
+        position: Vec2,
+        value: f32,
+    },
+    FoodDelivered {
+        ant: u32,
+        value: f32,
+    },
+    MarkerAdded {
+        marker_type: MarkerType,
+        position: Vec2,
+        intensity: f32,
+    },
+}
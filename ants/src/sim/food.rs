@@ -1,37 +1,179 @@
 use macroquad::{
-    prelude::{Vec2, GREEN},
+    prelude::{Color, Vec2, BROWN, GREEN, ORANGE},
     shapes::draw_circle,
 };
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FoodKind {
+    Seed,
+    Fruit,
+    Carcass,
+}
+
+impl FoodKind {
+    /// Nutritional value of a single item
+    pub fn value(&self) -> f32 {
+        match self {
+            FoodKind::Seed => 1.0,
+            FoodKind::Fruit => 5.0,
+            FoodKind::Carcass => 20.0,
+        }
+    }
+
+    /// Seconds an ant spends at the source before it can leave with an item
+    pub fn handling_time(&self) -> f32 {
+        match self {
+            FoodKind::Seed => 0.25,
+            FoodKind::Fruit => 1.5,
+            FoodKind::Carcass => 1.0,
+        }
+    }
+
+    /// Number of ants needed to lift a single item
+    pub fn carriers(&self) -> u32 {
+        match self {
+            FoodKind::Seed | FoodKind::Fruit => 1,
+            FoodKind::Carcass => 3,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        match self {
+            FoodKind::Seed => 4.0,
+            FoodKind::Fruit => 6.0,
+            FoodKind::Carcass => 8.0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            FoodKind::Seed => GREEN,
+            FoodKind::Fruit => ORANGE,
+            FoodKind::Carcass => BROWN,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            FoodKind::Seed => FoodKind::Fruit,
+            FoodKind::Fruit => FoodKind::Carcass,
+            FoodKind::Carcass => FoodKind::Seed,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Food {
     pub position: Vec2,
     pub radius: f32,
+    pub kind: FoodKind,
+    /// Sim time the source was added at
+    pub created: f32,
     quantity: f32,
+    capacity: f32,
+    /// Group of ants gathering to lift the next item
+    group: u32,
+    carriers: u32,
+    /// Shares of the lifted items not carried away yet
+    lifting: u32,
+    discovered: bool,
 }
 
 impl Food {
-    pub fn new(position: Vec2, radius: f32, quantity: f32) -> Self {
+    pub fn new(position: Vec2, kind: FoodKind, quantity: f32) -> Self {
         Self {
             position,
-            radius,
+            radius: kind.radius(),
+            kind,
+            created: 0.0,
             quantity,
+            capacity: quantity,
+            group: 0,
+            carriers: 0,
+            lifting: 0,
+            discovered: false,
         }
         //TODO: add marker
     }
 
+    pub fn quantity(&self) -> f32 {
+        self.quantity
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.quantity <= 0.0
+        !self.has_items() && self.lifting == 0
+    }
+
+    pub fn has_items(&self) -> bool {
+        self.quantity >= 1.0
     }
 
-    pub fn pick(&mut self) {
-        self.quantity -= 1.0;
-        if self.is_empty() {
-            //TODO: handle marker
+    /// Grows items back up to the initial quantity
+    pub fn regrow(&mut self, amount: f32) {
+        self.quantity = (self.quantity + amount).min(self.capacity);
+    }
+
+    /// Marks the source as found by an ant, returns how long it took the
+    /// first time
+    pub fn discover(&mut self, time: f32) -> Option<f32> {
+        if self.discovered {
+            return None;
+        }
+        self.discovered = true;
+        Some(time - self.created)
+    }
+
+    /// Registers an ant waiting to carry an item away, returning the group
+    /// it joined
+    pub fn grab(&mut self) -> u32 {
+        let group = self.group;
+        self.carriers += 1;
+        self.lift();
+        group
+    }
+
+    /// Unregisters an ant of `group` that gave up or left before carrying
+    /// its share away
+    pub fn release(&mut self, group: u32) {
+        if group == self.group {
+            self.carriers = self.carriers.saturating_sub(1);
+        } else {
+            self.lifting = self.lifting.saturating_sub(1);
+        }
+    }
+
+    /// Lifts an item once enough ants of the gathering group wait for it,
+    /// later ants gather in the next group
+    fn lift(&mut self) {
+        let needed = self.kind.carriers();
+        if self.has_items() && self.carriers >= needed {
+            self.quantity -= 1.0;
+            self.lifting += needed;
+            self.carriers -= needed;
+            self.group += 1;
+            if !self.has_items() {
+                //TODO: handle marker
+            }
+        }
+    }
+
+    /// Share of the item value carried by an ant of `group`, once its group
+    /// lifted an item
+    pub fn pick(&mut self, group: u32) -> Option<f32> {
+        if group < self.group {
+            self.lifting = self.lifting.saturating_sub(1);
+            Some(self.kind.value() / self.kind.carriers() as f32)
+        } else {
+            None
         }
     }
 
     pub fn draw(&self) {
-        draw_circle(self.position.x, self.position.y, self.radius, GREEN);
+        let mut color = self.kind.color();
+        if !self.has_items() {
+            // depleted sources waiting to regrow
+            color.a = 0.3;
+        }
+        draw_circle(self.position.x, self.position.y, self.radius, color);
     }
 }
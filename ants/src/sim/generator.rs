@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+use macroquad::prelude::{vec2, Vec2};
+
+use crate::{Config, MAP_NEST_CLEARING, MAP_PATCH_SPACING};
+
+use super::{spawn_cluster, FoodKind, Noise, TerrainKind, World};
+
+/// Replaces the terrain with a map grown from coherent noise seeded by
+/// `map_seed`: walls covering `map_obstacles` of the world, grass, sand and
+/// mud covering `map_terrain` of it, and `map_food_patches` food clusters
+/// the ants can reach from `nest`. Features are about `map_scale` pixels
+/// across and the same parameters always give the same map.
+pub fn generate_map(world: &mut World, nest: Vec2, cfg: &Config) {
+    let noise = Noise::new(cfg.map_seed as u32);
+    let (width, height) = world.terrain.size();
+    let cell_size = world.terrain.cell_size() as f32;
+    let centers: Vec<Vec2> = (0..width * height)
+        .map(|i| vec2((i % width) as f32 + 0.5, (i / width) as f32 + 0.5) * cell_size)
+        .collect();
+    // each layer of the noise drives one feature
+    let field = |layer: f32| -> Vec<f32> {
+        centers
+            .iter()
+            .map(|center| fractal(&noise, *center / cfg.map_scale, layer))
+            .collect()
+    };
+
+    let walls = field(0.0);
+    let wall_level = quantile(&walls, 1.0 - cfg.map_obstacles);
+    let costs = field(16.0);
+    let cost_level = quantile(&costs, 1.0 - cfg.map_terrain);
+    let cost_max = quantile(&costs, 1.0);
+
+    for (i, center) in centers.iter().enumerate() {
+        let kind = if center.distance(nest) < MAP_NEST_CLEARING {
+            TerrainKind::Ground
+        } else if walls[i] > wall_level {
+            TerrainKind::Wall
+        } else if costs[i] > cost_level {
+            // costlier terrain towards the peaks
+            match (costs[i] - cost_level) / (cost_max - cost_level) {
+                t if t < 1.0 / 3.0 => TerrainKind::Grass,
+                t if t < 2.0 / 3.0 => TerrainKind::Sand,
+                _ => TerrainKind::Mud,
+            }
+        } else {
+            TerrainKind::Ground
+        };
+        world.terrain.set(i % width, i / width, kind);
+    }
+
+    // food goes on the noise peaks reachable from the nest, keeping apart
+    let reachable = reachable_cells(world, nest);
+    let food = field(32.0);
+    let size = vec2(world.width as f32, world.height as f32);
+    let mut candidates: Vec<usize> = (0..centers.len()).filter(|i| reachable[*i]).collect();
+    candidates.sort_by(|a, b| food[*b].total_cmp(&food[*a]));
+
+    let mut patches: Vec<Vec2> = vec![];
+    for i in candidates {
+        if patches.len() >= cfg.map_food_patches as usize {
+            break;
+        }
+
+        let center = centers[i];
+        // room for the whole cluster, inside the world and off the walls
+        let clear = (-2..=2).all(|dx| {
+            (-2..=2).all(|dy| {
+                let pos = center + vec2(dx as f32, dy as f32) * cell_size;
+                pos.cmpge(Vec2::ZERO).all()
+                    && pos.cmplt(size).all()
+                    && world.terrain.get(pos) != TerrainKind::Wall
+            })
+        });
+        if clear
+            && center.distance(nest) >= MAP_PATCH_SPACING
+            && patches
+                .iter()
+                .all(|patch| patch.distance(center) >= MAP_PATCH_SPACING)
+        {
+            patches.push(center);
+        }
+    }
+
+    for patch in patches {
+        let kind = match noise.get(patch.x, patch.y, 48.0) {
+            n if n < 0.6 => FoodKind::Seed,
+            n if n < 0.85 => FoodKind::Fruit,
+            _ => FoodKind::Carcass,
+        };
+        spawn_cluster(world, patch, kind);
+    }
+}
+
+/// Noise summed over three octaves, still in `[0, 1]`
+fn fractal(noise: &Noise, pos: Vec2, layer: f32) -> f32 {
+    let mut value = 0.0;
+    let mut amplitude = 1.0;
+    let mut scale = 1.0;
+    for _ in 0..3 {
+        value += noise.get(pos.x * scale, pos.y * scale, layer) * amplitude;
+        amplitude *= 0.5;
+        scale *= 2.0;
+    }
+    value / 1.75
+}
+
+/// Value below which `share` of the values lie
+fn quantile(values: &[f32], share: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let i = ((sorted.len() - 1) as f32 * share.clamp(0.0, 1.0)).round() as usize;
+    sorted[i]
+}
+
+/// Flood fills the non wall terrain cells from `start`
+fn reachable_cells(world: &World, start: Vec2) -> Vec<bool> {
+    let (width, height) = world.terrain.size();
+    let cell_size = world.terrain.cell_size() as f32;
+    let is_wall = |x: usize, y: usize| {
+        let center = vec2(x as f32 + 0.5, y as f32 + 0.5) * cell_size;
+        world.terrain.get(center) == TerrainKind::Wall
+    };
+
+    let mut reachable = vec![false; width * height];
+    let (x, y) = (
+        ((start.x / cell_size) as usize).min(width - 1),
+        ((start.y / cell_size) as usize).min(height - 1),
+    );
+    let mut queue = VecDeque::from([(x, y)]);
+    reachable[y * width + x] = true;
+    while let Some((x, y)) = queue.pop_front() {
+        for (nx, ny) in [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ] {
+            if nx < width && ny < height && !reachable[ny * width + nx] && !is_wall(nx, ny) {
+                reachable[ny * width + nx] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    reachable
+}
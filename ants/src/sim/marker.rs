@@ -3,7 +3,9 @@ use macroquad::{
     texture::Image,
 };
 
-#[derive(Clone, Copy, Debug)]
+use crate::MARKER_FIELD_CELL_SIZE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MarkerType {
     ToFood,
     ToHome,
@@ -35,6 +37,29 @@ impl Marker {
         }
     }
 
+    pub fn is_permanent(&self) -> bool {
+        self.permanent
+    }
+
+    /// Absorbs another deposit, summing intensities and moving to their
+    /// intensity weighted position
+    pub fn merge(&mut self, other: &Marker) {
+        let total = self.intensity + other.intensity;
+        if total > 0.0 {
+            self.position =
+                (self.position * self.intensity + other.position * other.intensity) / total;
+        }
+        self.intensity = total;
+        self.initial_intensity += other.initial_intensity;
+    }
+
+    /// Moves the deposit, permanent markers stay in place
+    pub fn drift(&mut self, offset: Vec2) {
+        if !self.permanent {
+            self.position += offset;
+        }
+    }
+
     pub fn draw(&self, img: &mut Image) {
         let color = match self.marker_type {
             MarkerType::ToFood => {
@@ -44,6 +69,78 @@ impl Marker {
                 Color::new(0.00, 0.47, 0.95, self.intensity / self.initial_intensity)
             }
         };
-        img.set_pixel(self.position.x as u32, self.position.y as u32, color);
+        let (x, y) = (self.position.x as u32, self.position.y as u32);
+        if (x as usize) < img.width() && (y as usize) < img.height() {
+            img.set_pixel(x, y, color);
+        }
+    }
+}
+
+/// Marker intensity summed on a fine grid along with its intensity weighted
+/// position, so sensing markers costs the same however many were dropped
+pub struct MarkerField {
+    width: usize,
+    height: usize,
+    intensity: Vec<f32>,
+    weighted: Vec<Vec2>,
+}
+
+impl MarkerField {
+    pub fn new(width: usize, height: usize) -> Self {
+        let width = width.div_ceil(MARKER_FIELD_CELL_SIZE);
+        let height = height.div_ceil(MARKER_FIELD_CELL_SIZE);
+        Self {
+            width,
+            height,
+            intensity: vec![0.0; width * height],
+            weighted: vec![Vec2::ZERO; width * height],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.intensity.fill(0.0);
+        self.weighted.fill(Vec2::ZERO);
+    }
+
+    pub fn add(&mut self, marker: &Marker) {
+        if marker.intensity <= 0.0 || marker.position.x < 0.0 || marker.position.y < 0.0 {
+            return;
+        }
+        let x = marker.position.x as usize / MARKER_FIELD_CELL_SIZE;
+        let y = marker.position.y as usize / MARKER_FIELD_CELL_SIZE;
+        if x < self.width && y < self.height {
+            self.intensity[y * self.width + x] += marker.intensity;
+            self.weighted[y * self.width + x] += marker.position * marker.intensity;
+        }
+    }
+
+    /// Total intensity and intensity weighted position of the cells whose
+    /// markers are on average within `radius` of `pos` and ahead along `dir`
+    pub fn sense(&self, pos: Vec2, dir: Vec2, radius: f32) -> (f32, Vec2) {
+        let cell_size = MARKER_FIELD_CELL_SIZE as f32;
+        let min = ((pos - radius) / cell_size).max(Vec2::ZERO);
+        let max = (pos + radius) / cell_size;
+        let (max_x, max_y) = (
+            (max.x as usize).min(self.width - 1),
+            (max.y as usize).min(self.height - 1),
+        );
+
+        let mut total = 0.0;
+        let mut point = Vec2::ZERO;
+        for y in min.y as usize..=max_y {
+            for x in min.x as usize..=max_x {
+                let index = y * self.width + x;
+                let intensity = self.intensity[index];
+                if intensity <= 0.0 {
+                    continue;
+                }
+                let to_cell = self.weighted[index] / intensity - pos;
+                if to_cell.length() < radius && to_cell.dot(dir) > 0.0 {
+                    total += intensity;
+                    point += self.weighted[index];
+                }
+            }
+        }
+        (total, point)
     }
 }
@@ -1,11 +1,29 @@
 mod ant;
+mod brood;
 mod colony;
+mod crowding;
+mod daylight;
+mod event;
 mod food;
+mod generator;
 mod marker;
+mod noise;
+mod spawner;
+mod terrain;
+mod wind;
 mod world;
 
 pub use ant::*;
+pub use brood::*;
 pub use colony::*;
+pub use crowding::*;
+pub use daylight::*;
+pub use event::*;
 pub use food::*;
+pub use generator::*;
 pub use marker::*;
+pub use noise::*;
+pub use spawner::*;
+pub use terrain::*;
+pub use wind::*;
 pub use world::*;
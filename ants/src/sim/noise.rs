@@ -0,0 +1,39 @@
+/// Seeded value noise, smoothly interpolated between random values placed
+/// on an integer lattice.
+pub struct Noise {
+    seed: u32,
+}
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    fn hash(&self, x: i32, y: i32, z: i32) -> f32 {
+        let mut h = self.seed
+            ^ (x as u32).wrapping_mul(0x8da6_b343)
+            ^ (y as u32).wrapping_mul(0xd816_3841)
+            ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+        h = (h ^ (h >> 13)).wrapping_mul(0x5bd1_e995);
+        h ^= h >> 15;
+        h as f32 / u32::MAX as f32
+    }
+
+    /// Noise value in `[0, 1]` at the given point
+    pub fn get(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, ty, tz) = (smooth(x - x0), smooth(y - y0), smooth(z - z0));
+        let (x0, y0, z0) = (x0 as i32, y0 as i32, z0 as i32);
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |z: i32| {
+            lerp(
+                lerp(self.hash(x0, y0, z), self.hash(x0 + 1, y0, z), tx),
+                lerp(self.hash(x0, y0 + 1, z), self.hash(x0 + 1, y0 + 1, z), tx),
+                ty,
+            )
+        };
+        lerp(plane(z0), plane(z0 + 1), tz)
+    }
+}
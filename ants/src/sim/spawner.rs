@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+
+use macroquad::{
+    prelude::{vec2, Vec2},
+    rand,
+};
+
+use crate::{Config, FOOD_SPAWN_NOISE_SCALE, MAX_SPAWNED_CLUSTERS};
+
+use super::{Food, FoodKind, Noise, TerrainKind, World};
+
+/// Drops new food clusters at random locations, `food_spawn_rate` clusters
+/// per second. With `food_spawn_patchiness` above zero, locations are
+/// rejection sampled against coherent noise so clusters gather in patches.
+/// Past `MAX_SPAWNED_CLUSTERS` the oldest spawned cluster is removed, so
+/// regrowing sources don't pile up.
+pub struct FoodSpawner {
+    noise: Noise,
+    /// Center and kind of the live spawned clusters, oldest first
+    clusters: VecDeque<(Vec2, FoodKind)>,
+}
+
+impl FoodSpawner {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            noise: Noise::new(seed),
+            clusters: VecDeque::new(),
+        }
+    }
+
+    pub fn update(&mut self, dt: f32, world: &mut World, cfg: &Config) {
+        if rand::gen_range(0.0, 1.0) >= cfg.food_spawn_rate * dt {
+            return;
+        }
+
+        if let Some(position) = self.find_location(world, cfg) {
+            let kind = match rand::gen_range(0, 10) {
+                0 => FoodKind::Carcass,
+                1..=3 => FoodKind::Fruit,
+                _ => FoodKind::Seed,
+            };
+            spawn_cluster(world, position, kind);
+            self.clusters.push_back((position, kind));
+            if self.clusters.len() > MAX_SPAWNED_CLUSTERS {
+                if let Some((position, kind)) = self.clusters.pop_front() {
+                    // reaches the corners of the 5x5 block
+                    world.remove_food(position, kind.radius() * 3.0);
+                }
+            }
+        }
+    }
+
+    fn find_location(&self, world: &World, cfg: &Config) -> Option<Vec2> {
+        for _ in 0..10 {
+            let position = vec2(
+                rand::gen_range(0.0, world.width as f32),
+                rand::gen_range(0.0, world.height as f32),
+            );
+            if world.terrain.get(position) == TerrainKind::Wall {
+                continue;
+            }
+
+            let n = self.noise.get(
+                position.x * FOOD_SPAWN_NOISE_SCALE,
+                position.y * FOOD_SPAWN_NOISE_SCALE,
+                0.0,
+            );
+            let patch = ((n - 0.5) * 4.0).clamp(0.0, 1.0);
+            let p = cfg.food_spawn_patchiness;
+            if rand::gen_range(0.0, 1.0) < (1.0 - p) + p * patch {
+                return Some(position);
+            }
+        }
+        None
+    }
+}
+
+/// Adds a 5x5 block of food sources around `position`
+pub fn spawn_cluster(world: &mut World, position: Vec2, kind: FoodKind) {
+    let spacing = kind.radius();
+    for x in -2..=2 {
+        for y in -2..=2 {
+            let offset = vec2(x as f32, y as f32) * spacing;
+            world.add_food(Food::new(position + offset, kind, 10.));
+        }
+    }
+}
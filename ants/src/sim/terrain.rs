@@ -0,0 +1,219 @@
+use std::{fs, io};
+
+use macroquad::{
+    prelude::{vec2, Color, Vec2, WHITE},
+    texture::{draw_texture_ex, DrawTextureParams, FilterMode, Image, Texture2D},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrainKind {
+    Ground,
+    Grass,
+    Sand,
+    Mud,
+    Wall,
+}
+
+impl TerrainKind {
+    /// Movement cost of crossing the cell, ants move at `move_speed / cost`
+    pub fn cost(&self) -> f32 {
+        match self {
+            TerrainKind::Ground => 1.0,
+            TerrainKind::Grass => 1.25,
+            TerrainKind::Sand => 2.0,
+            TerrainKind::Mud => 4.0,
+            TerrainKind::Wall => f32::INFINITY,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            TerrainKind::Ground => Color::new(0.0, 0.0, 0.0, 0.0),
+            TerrainKind::Grass => Color::new(0.10, 0.25, 0.08, 1.0),
+            TerrainKind::Sand => Color::new(0.30, 0.27, 0.15, 1.0),
+            TerrainKind::Mud => Color::new(0.22, 0.14, 0.08, 1.0),
+            TerrainKind::Wall => Color::new(0.45, 0.45, 0.45, 1.0),
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            TerrainKind::Ground => TerrainKind::Grass,
+            TerrainKind::Grass => TerrainKind::Sand,
+            TerrainKind::Sand => TerrainKind::Mud,
+            TerrainKind::Mud => TerrainKind::Wall,
+            TerrainKind::Wall => TerrainKind::Ground,
+        }
+    }
+
+    fn from_char(c: char) -> Self {
+        match c {
+            'g' => TerrainKind::Grass,
+            's' => TerrainKind::Sand,
+            'm' => TerrainKind::Mud,
+            '#' => TerrainKind::Wall,
+            _ => TerrainKind::Ground,
+        }
+    }
+}
+
+pub struct Terrain {
+    width: usize,
+    height: usize,
+    cell_size: usize,
+    cells: Vec<TerrainKind>,
+    /// Created on first draw, so the terrain can be used without a window
+    texture: Option<Texture2D>,
+    img: Image,
+    dirty: bool,
+}
+
+impl Terrain {
+    pub fn new(_width: usize, _height: usize, cell_size: usize) -> Self {
+        let width = _width / cell_size + 1;
+        let height = _height / cell_size + 1;
+        let img = Image::gen_image_color(width as u16, height as u16, TerrainKind::Ground.color());
+
+        Self {
+            width,
+            height,
+            cell_size,
+            cells: vec![TerrainKind::Ground; width * height],
+            texture: None,
+            img,
+            dirty: false,
+        }
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let mut resized = Terrain::new(width, height, self.cell_size);
+        for x in 0..self.width.min(resized.width) {
+            for y in 0..self.height.min(resized.height) {
+                resized.set(x, y, self.cells[y * self.width + x]);
+            }
+        }
+
+        if let Some(texture) = self.texture {
+            texture.delete();
+        }
+        *self = resized;
+    }
+
+    /// Number of cells across and down
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn cell_size(&self) -> usize {
+        self.cell_size
+    }
+
+    fn get_cell_coords(&self, pos: Vec2) -> Option<(usize, usize)> {
+        if pos.x < 0.0 || pos.y < 0.0 {
+            return None;
+        }
+
+        let x = pos.x as usize / self.cell_size;
+        let y = pos.y as usize / self.cell_size;
+        if x < self.width && y < self.height {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, pos: Vec2) -> TerrainKind {
+        match self.get_cell_coords(pos) {
+            Some((x, y)) => self.cells[y * self.width + x],
+            None => TerrainKind::Ground,
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, kind: TerrainKind) {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x] = kind;
+            self.img.set_pixel(x as u32, y as u32, kind.color());
+            self.dirty = true;
+        }
+    }
+
+    /// Speed multiplier for an ant standing at `pos`, ground speed inside a
+    /// wall so an ant caught under a freshly painted one can walk out
+    pub fn speed_at(&self, pos: Vec2) -> f32 {
+        match self.get(pos) {
+            TerrainKind::Wall => 1.0,
+            kind => 1.0 / kind.cost(),
+        }
+    }
+
+    pub fn paint(&mut self, pos: Vec2, radius: f32, kind: TerrainKind) {
+        let cell_size = self.cell_size as f32;
+        let min = ((pos - radius) / cell_size).max(Vec2::ZERO);
+        let max = (pos + radius) / cell_size;
+
+        for x in min.x as usize..=max.x as usize {
+            for y in min.y as usize..=max.y as usize {
+                let center = vec2(x as f32 + 0.5, y as f32 + 0.5) * cell_size;
+                if center.distance(pos) <= radius {
+                    self.set(x, y, kind);
+                }
+            }
+        }
+    }
+
+    pub fn paint_rect(&mut self, min: Vec2, max: Vec2, kind: TerrainKind) {
+        let cell_size = self.cell_size as f32;
+        let (min, max) = (min.min(max), max.max(min));
+        let min = (min / cell_size).max(Vec2::ZERO);
+        let max = max / cell_size;
+
+        for x in min.x as usize..=max.x as usize {
+            for y in min.y as usize..=max.y as usize {
+                self.set(x, y, kind);
+            }
+        }
+    }
+
+    /// Loads a text map where each line is a row of cells:
+    /// `.` ground, `g` grass, `s` sand, `m` mud and `#` wall.
+    pub fn load_map(&mut self, path: &str) -> io::Result<()> {
+        let map = fs::read_to_string(path)?;
+        for (y, line) in map.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                self.set(x, y, TerrainKind::from_char(c));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn draw(&mut self) {
+        let texture = match self.texture {
+            Some(texture) if self.dirty => {
+                texture.update(&self.img);
+                texture
+            }
+            Some(texture) => texture,
+            None => {
+                let texture = Texture2D::from_image(&self.img);
+                texture.set_filter(FilterMode::Nearest);
+                self.texture = Some(texture);
+                texture
+            }
+        };
+        self.dirty = false;
+
+        draw_texture_ex(
+            texture,
+            0.,
+            0.,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(
+                    (self.width * self.cell_size) as f32,
+                    (self.height * self.cell_size) as f32,
+                )),
+                ..Default::default()
+            },
+        );
+    }
+}
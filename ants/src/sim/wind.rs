@@ -0,0 +1,61 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::{vec2, Vec2};
+
+use crate::{Config, WIND_NOISE_SCALE, WIND_NOISE_SPEED};
+
+use super::Noise;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindMode {
+    Off,
+    Uniform,
+    Noise,
+}
+
+impl WindMode {
+    pub fn next(&self) -> Self {
+        match self {
+            WindMode::Off => WindMode::Uniform,
+            WindMode::Uniform => WindMode::Noise,
+            WindMode::Noise => WindMode::Off,
+        }
+    }
+}
+
+/// Wind blowing `wind_strength` pixels per second towards `wind_direction`,
+/// in noise mode the direction swirls across the world and over time.
+pub struct Wind {
+    noise: Noise,
+    time: f32,
+}
+
+impl Wind {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            noise: Noise::new(seed),
+            time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
+
+    pub fn at(&self, pos: Vec2, cfg: &Config) -> Vec2 {
+        let angle = match cfg.wind {
+            WindMode::Off => return Vec2::ZERO,
+            WindMode::Uniform => cfg.wind_direction,
+            WindMode::Noise => {
+                let n = self.noise.get(
+                    pos.x * WIND_NOISE_SCALE,
+                    pos.y * WIND_NOISE_SCALE,
+                    self.time * WIND_NOISE_SPEED,
+                );
+                cfg.wind_direction + (n - 0.5) * 2.0 * PI
+            }
+        };
+
+        vec2(angle.cos(), angle.sin()) * cfg.wind_strength
+    }
+}
@@ -1,13 +1,16 @@
 use macroquad::{
-    prelude::{Vec2, BLACK, RED, WHITE},
+    prelude::{Color, Vec2, RED, WHITE},
+    rand,
     shapes::draw_rectangle_lines,
     texture::{draw_texture, Image, Texture2D},
-    window::screen_width,
 };
 
-use crate::{Config, MAX_MARKER_PER_CELL};
+use crate::{
+    Config, DISCOVERY_RADIUS, MARKER_CELL_SIZE, MARKER_MERGE_RADIUS, MAX_MARKER_PER_CELL,
+    TERRAIN_CELL_SIZE,
+};
 
-use super::{Food, Marker, MarkerType};
+use super::{DayCycle, Food, Marker, MarkerField, MarkerType, SimEvent, Terrain, Wind, WindMode};
 
 pub struct Grid<T> {
     width: usize,
@@ -18,8 +21,9 @@ pub struct Grid<T> {
 
 impl<T: Clone> Grid<T> {
     fn new(_width: usize, _height: usize, cell_size: usize) -> Self {
-        let width = _width / cell_size;
-        let height = _height / cell_size;
+        // round up so the last partial row and column are covered too
+        let width = _width.div_ceil(cell_size);
+        let height = _height.div_ceil(cell_size);
 
         Self {
             width,
@@ -44,67 +48,188 @@ impl<T: Clone> Grid<T> {
         (x, y)
     }
 
-    fn add(&mut self, pos: Vec2, value: T) {
+    fn get_cell_mut(&mut self, pos: Vec2) -> Option<&mut Vec<T>> {
+        if pos.x < 0.0 || pos.y < 0.0 {
+            return None;
+        }
+
         let (x, y) = self.get_cell_coords(pos);
         if self.check_bounds(x, y) {
             let index = self.get_index(x, y);
-            if self.cells[index].len() < MAX_MARKER_PER_CELL {
-                self.cells[index].push(value);
+            Some(&mut self.cells[index])
+        } else {
+            None
+        }
+    }
+
+    fn add(&mut self, pos: Vec2, value: T) {
+        if let Some(cell) = self.get_cell_mut(pos) {
+            if cell.len() < MAX_MARKER_PER_CELL {
+                cell.push(value);
             }
         }
     }
 
-    pub fn get_all_at(&self, pos: Vec2) -> Vec<T> {
-        let (x, y) = self.get_cell_coords(pos);
+    /// Moves values whose position left their cell into the right one,
+    /// dropping those that left the grid.
+    fn relocate(&mut self, position: impl Fn(&T) -> Vec2) {
+        let (width, cell_size) = (self.width, self.cell_size);
+        let mut moved = vec![];
 
-        let mut result = vec![];
-
-        for dx in 1..=3 {
-            for dy in 1..=3 {
-                let x = (x as i32 + (dx - 2)) as usize;
-                let y = (y as i32 + (dy - 2)) as usize;
-                if self.check_bounds(x, y) {
-                    let index = self.get_index(x, y);
-                    for cell in self.cells[index].iter() {
-                        result.push(cell.clone());
-                    }
+        for (index, cell_list) in self.cells.iter_mut().enumerate() {
+            cell_list.retain(|value| {
+                let pos = position(value);
+                let stays = pos.x >= 0.0
+                    && pos.y >= 0.0
+                    && pos.x as usize / cell_size == index % width
+                    && pos.y as usize / cell_size == index / width;
+                if !stays {
+                    moved.push(value.clone());
                 }
-            }
+                stays
+            });
         }
-        return result;
-    }
 
-    pub fn get_all_at_mut(&mut self, pos: Vec2) -> Vec<&mut T> {
-        let (x, y) = self.get_cell_coords(pos);
-        if self.check_bounds(x, y) {
-            let index = self.get_index(x, y);
-            self.cells[index].iter_mut().collect()
-        } else {
-            vec![]
+        for value in moved {
+            self.add(position(&value), value);
         }
     }
+
+    /// Iterates over every value in the grid
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter().flatten()
+    }
+
+    /// Iterates over the cell containing `pos`
+    pub fn iter_at_mut(&mut self, pos: Vec2) -> impl Iterator<Item = &mut T> {
+        self.get_cell_mut(pos).into_iter().flatten()
+    }
 }
 
 pub struct World {
+    pub width: usize,
+    pub height: usize,
     grid_home_markers: Grid<Marker>,
     grid_food_markers: Grid<Marker>,
+    /// What ants sense of the markers, kept in step with the grids
+    home_field: MarkerField,
+    food_field: MarkerField,
     pub grid_food: Grid<Food>,
-    texture: Texture2D,
+    pub terrain: Terrain,
+    pub wind: Wind,
+    pub day: DayCycle,
+    /// Sim time, for timing food discoveries
+    pub time: f32,
+    /// Food patches found by an ant and the total time they took to find
+    pub discoveries: usize,
+    discovery_time: f32,
+    discovered: Vec<Vec2>,
+    /// Merge new markers into nearby ones instead of dropping them once a
+    /// cell is full
+    pub merge_markers: bool,
+    pub markers_merged: usize,
+    pub markers_dropped: usize,
+    /// Events of the current step, only kept while recording
+    events: Option<Vec<SimEvent>>,
+    /// Created on first draw, so the world can be simulated without a window
+    texture: Option<Texture2D>,
     img: Image,
 }
 
 impl World {
+    /// Checks a world size is at least a pixel and fits the `u16` images
+    pub fn check_size(width: usize, height: usize) -> Result<(), String> {
+        let max = u16::MAX as usize;
+        if width == 0 || height == 0 || width > max || height > max {
+            return Err(format!(
+                "invalid size {}x{}, sides go from 1 to {} pixels",
+                width, height, max
+            ));
+        }
+        Ok(())
+    }
+
     pub fn new(width: usize, height: usize) -> Self {
-        let img = Image::gen_image_color(width as u16, height as u16, BLACK);
         Self {
-            grid_home_markers: Grid::new(width + 45, height, 45),
-            grid_food_markers: Grid::new(width + 45, height, 45),
+            width,
+            height,
+            grid_home_markers: Grid::new(width, height, MARKER_CELL_SIZE),
+            grid_food_markers: Grid::new(width, height, MARKER_CELL_SIZE),
+            home_field: MarkerField::new(width, height),
+            food_field: MarkerField::new(width, height),
             grid_food: Grid::new(width, height, 5),
-            texture: Texture2D::from_image(&img),
-            img,
+            terrain: Terrain::new(width, height, TERRAIN_CELL_SIZE),
+            wind: Wind::new(rand::rand()),
+            day: DayCycle::default(),
+            time: 0.0,
+            discoveries: 0,
+            discovery_time: 0.0,
+            discovered: vec![],
+            merge_markers: false,
+            markers_merged: 0,
+            markers_dropped: 0,
+            events: None,
+            texture: None,
+            img: Self::marker_image(width, height),
         }
     }
 
+    fn marker_image(width: usize, height: usize) -> Image {
+        Image::gen_image_color(width as u16, height as u16, Color::new(0., 0., 0., 0.))
+    }
+
+    /// Rebuilds grids, terrain and the marker texture for a new world size,
+    /// keeping everything that still fits inside it.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+
+        for marker_type in [MarkerType::ToFood, MarkerType::ToHome] {
+            let grid = self.get_grid_mut(marker_type);
+            let old = std::mem::replace(grid, Grid::new(width, height, MARKER_CELL_SIZE));
+            for marker in old.cells.into_iter().flatten() {
+                grid.add(marker.position, marker);
+            }
+        }
+
+        self.home_field = MarkerField::new(width, height);
+        self.food_field = MarkerField::new(width, height);
+        self.update_fields();
+
+        let old = std::mem::replace(&mut self.grid_food, Grid::new(width, height, 5));
+        for food in old.cells.into_iter().flatten() {
+            self.grid_food.add(food.position, food);
+        }
+
+        self.terrain.resize(width, height);
+
+        if let Some(texture) = self.texture.take() {
+            texture.delete();
+        }
+        self.img = Self::marker_image(width, height);
+    }
+
+    /// Starts keeping events for [`World::take_events`]
+    pub fn record_events(&mut self) {
+        self.events.get_or_insert_with(Vec::new);
+    }
+
+    /// Stops keeping events, dropping those not taken yet
+    pub fn stop_events(&mut self) {
+        self.events = None;
+    }
+
+    pub fn log(&mut self, event: SimEvent) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+
+    /// Events logged since the last call
+    pub fn take_events(&mut self) -> Vec<SimEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
     pub fn get_grid(&self, marker_type: MarkerType) -> &Grid<Marker> {
         match marker_type {
             MarkerType::ToFood => &self.grid_food_markers,
@@ -112,6 +237,38 @@ impl World {
         }
     }
 
+    pub fn get_field(&self, marker_type: MarkerType) -> &MarkerField {
+        match marker_type {
+            MarkerType::ToFood => &self.food_field,
+            MarkerType::ToHome => &self.home_field,
+        }
+    }
+
+    fn get_field_mut(&mut self, marker_type: MarkerType) -> &mut MarkerField {
+        match marker_type {
+            MarkerType::ToFood => &mut self.food_field,
+            MarkerType::ToHome => &mut self.home_field,
+        }
+    }
+
+    /// Sums the markers into the fields again, after markers were removed
+    /// or moved to new grids
+    fn update_fields(&mut self) {
+        for (grid, field) in [
+            (&self.grid_home_markers, &mut self.home_field),
+            (&self.grid_food_markers, &mut self.food_field),
+        ] {
+            field.clear();
+            for marker in grid.iter() {
+                field.add(marker);
+            }
+        }
+    }
+
+    pub fn marker_count(&self, marker_type: MarkerType) -> usize {
+        self.get_grid(marker_type).cells.iter().map(Vec::len).sum()
+    }
+
     fn get_grid_mut(&mut self, marker_type: MarkerType) -> &mut Grid<Marker> {
         match marker_type {
             MarkerType::ToFood => &mut self.grid_food_markers,
@@ -120,11 +277,54 @@ impl World {
     }
 
     pub fn add_marker(&mut self, marker: Marker) {
-        let grid = self.get_grid_mut(marker.marker_type);
-        grid.add(marker.position, marker);
+        self.log(SimEvent::MarkerAdded {
+            marker_type: marker.marker_type,
+            position: marker.position,
+            intensity: marker.intensity,
+        });
+
+        let merge = self.merge_markers;
+        let Some(cell) = self
+            .get_grid_mut(marker.marker_type)
+            .get_cell_mut(marker.position)
+        else {
+            return;
+        };
+
+        let full = cell.len() >= MAX_MARKER_PER_CELL;
+        if merge {
+            // nearest marker, within the merge radius unless the cell is full
+            let nearest = cell
+                .iter_mut()
+                .filter(|cell| !cell.is_permanent())
+                .map(|cell| (cell.position.distance(marker.position), cell))
+                .filter(|(distance, _)| full || *distance < MARKER_MERGE_RADIUS)
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+
+            if let Some((_, nearest)) = nearest {
+                nearest.merge(&marker);
+                self.markers_merged += 1;
+                // merging keeps the summed intensity and weighted position
+                self.get_field_mut(marker.marker_type).add(&marker);
+                return;
+            }
+        }
+
+        if full {
+            self.markers_dropped += 1;
+        } else {
+            cell.push(marker);
+            self.get_field_mut(marker.marker_type).add(&marker);
+        }
     }
 
-    pub fn add_food(&mut self, food: Food) {
+    pub fn add_food(&mut self, mut food: Food) {
+        food.created = self.time;
+        self.log(SimEvent::FoodAdded {
+            position: food.position,
+            kind: food.kind,
+            quantity: food.quantity(),
+        });
         self.grid_food.add(food.position, food);
         self.add_marker(Marker::new(
             food.position,
@@ -134,6 +334,35 @@ impl World {
         ));
     }
 
+    pub fn remove_markers(&mut self, marker_type: MarkerType, pos: Vec2, radius: f32) {
+        let grid = self.get_grid_mut(marker_type);
+        for cell_list in &mut grid.cells {
+            cell_list.retain(|cell| cell.position.distance(pos) > radius);
+        }
+        self.update_fields();
+    }
+
+    /// Removes the food around `pos` along with the markers left on it, so
+    /// ants stop heading to the empty spot
+    pub fn remove_food(&mut self, pos: Vec2, radius: f32) {
+        for cell_list in &mut self.grid_food.cells {
+            cell_list.retain(|cell| cell.position.distance(pos) > radius);
+        }
+        for cell_list in &mut self.grid_food_markers.cells {
+            cell_list.retain(|cell| cell.is_permanent() || cell.position.distance(pos) > radius);
+        }
+        self.update_fields();
+    }
+
+    /// Removes food and non permanent markers around `pos`
+    pub fn erase(&mut self, pos: Vec2, radius: f32) {
+        self.remove_food(pos, radius);
+        for cell_list in &mut self.grid_home_markers.cells {
+            cell_list.retain(|cell| cell.is_permanent() || cell.position.distance(pos) > radius);
+        }
+        self.update_fields();
+    }
+
     fn remove_expired_markers(&mut self) {
         for cell_list in &mut self.grid_home_markers.cells {
             cell_list.retain(|cell| cell.intensity > 0.0);
@@ -144,31 +373,83 @@ impl World {
         }
     }
 
+    /// Counts a food source found `delay` seconds after it appeared, unless
+    /// it belongs to a patch found before
+    pub fn record_discovery(&mut self, position: Vec2, delay: f32) {
+        if self
+            .discovered
+            .iter()
+            .any(|found| found.distance(position) < DISCOVERY_RADIUS)
+        {
+            return;
+        }
+        self.discovered.push(position);
+        self.discoveries += 1;
+        self.discovery_time += delay;
+    }
+
+    /// Mean time from a food patch appearing to an ant finding it
+    pub fn mean_discovery_time(&self) -> Option<f32> {
+        (self.discoveries > 0).then(|| self.discovery_time / self.discoveries as f32)
+    }
+
     fn remove_expired_food(&mut self) {
         for cell_list in &mut self.grid_food.cells {
             cell_list.retain(|cell| !cell.is_empty());
         }
     }
 
-    pub fn update(&mut self, dt: f32) {
+    pub fn update(&mut self, dt: f32, cfg: &Config) {
         self.remove_expired_markers();
-        self.remove_expired_food();
+        if cfg.food_regrowth > 0.0 {
+            for cell_list in &mut self.grid_food.cells {
+                for cell in cell_list {
+                    cell.regrow(cfg.food_regrowth * dt);
+                }
+            }
+        } else {
+            self.remove_expired_food();
+        }
 
-        for cell_list in &mut self.grid_home_markers.cells {
-            for cell in cell_list {
-                cell.update(dt);
+        self.wind.update(dt);
+        if cfg.wind != WindMode::Off {
+            self.drift_markers(dt, cfg);
+        }
+
+        self.time += dt;
+        self.day.update(dt);
+        let evaporation = self.day.scale(cfg.night_evaporation, cfg);
+        // the fields are summed again along the way
+        for (grid, field) in [
+            (&mut self.grid_home_markers, &mut self.home_field),
+            (&mut self.grid_food_markers, &mut self.food_field),
+        ] {
+            field.clear();
+            for cell in grid.cells.iter_mut().flatten() {
+                cell.update(dt * evaporation);
+                field.add(cell);
             }
         }
+    }
 
-        for cell_list in &mut self.grid_food_markers.cells {
-            for cell in cell_list {
-                cell.update(dt);
+    /// Advects marker deposits with the wind
+    fn drift_markers(&mut self, dt: f32, cfg: &Config) {
+        for grid in [&mut self.grid_home_markers, &mut self.grid_food_markers] {
+            for cell_list in &mut grid.cells {
+                for cell in cell_list {
+                    cell.drift(self.wind.at(cell.position, cfg) * dt);
+                }
             }
+            grid.relocate(|marker| marker.position);
         }
     }
 
     pub fn draw(&mut self, cfg: &Config) {
+        self.terrain.draw();
+
         if cfg.draw_markers {
+            // markers move with the wind, start from a clean image
+            self.img.get_image_data_mut().fill([0, 0, 0, 0]);
             for cell_list in &self.grid_home_markers.cells {
                 for cell in cell_list {
                     cell.draw(&mut self.img);
@@ -181,8 +462,12 @@ impl World {
                 }
             }
 
-            self.texture.update(&self.img);
-            draw_texture(self.texture, 0., 0., WHITE);
+            let img = &self.img;
+            let texture = *self
+                .texture
+                .get_or_insert_with(|| Texture2D::from_image(img));
+            texture.update(&self.img);
+            draw_texture(texture, 0., 0., WHITE);
         }
 
         for cell_list in &self.grid_food.cells {
@@ -194,10 +479,8 @@ impl World {
         if cfg.draw_grid {
             for i in 0..self.grid_food_markers.cells.len() {
                 // draw grid
-                let x =
-                    i % ((screen_width() + 45.) / self.grid_food_markers.cell_size as f32) as usize;
-                let y =
-                    i / ((screen_width() + 45.) / self.grid_food_markers.cell_size as f32) as usize;
+                let x = i % self.grid_food_markers.width;
+                let y = i / self.grid_food_markers.width;
                 draw_rectangle_lines(
                     (x * self.grid_food_markers.cell_size) as f32,
                     (y * self.grid_food_markers.cell_size) as f32,
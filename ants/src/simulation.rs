@@ -0,0 +1,202 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    str::FromStr,
+};
+
+use macroquad::prelude::{rand, vec2, Color};
+
+use crate::{
+    arg_value,
+    recording::Recorder,
+    replay::{Input, InputRecorder, Replay},
+    scenario::Scenario,
+    sim::{generate_map, Colony, Food, FoodKind, FoodSpawner, World},
+    Config, ANT_COUNT, TIME_STEP, WORLD_HEIGHT, WORLD_WIDTH,
+};
+
+/// Everything needed to step the simulation, without any drawing so it can
+/// also run headless.
+pub struct Simulation {
+    pub world: World,
+    pub colony: Colony,
+    pub cfg: Config,
+    pub spawner: FoodSpawner,
+    pub scenario: Option<Scenario>,
+    pub recorder: Option<Recorder<BufWriter<File>>>,
+    pub inputs: Option<InputRecorder>,
+    pub replay: Option<Replay>,
+    pub tick: u32,
+    pub time: f32,
+}
+
+impl Simulation {
+    /// Builds the starting world from the command line options
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let (width, height) = match arg_value(args, "--size") {
+            Some(size) => {
+                let (width, height) = size
+                    .split_once('x')
+                    .ok_or_else(|| format!("expected `<width>x<height>` in `{}`", size))?;
+                let size = (parse("width", width)?, parse("height", height)?);
+                World::check_size(size.0, size.1)?;
+                size
+            }
+            None => (WORLD_WIDTH, WORLD_HEIGHT),
+        };
+
+        let mut cfg = Config {
+            path_integration: args.iter().any(|arg| arg == "--homing"),
+            crowding: args.iter().any(|arg| arg == "--crowding"),
+            energy: args.iter().any(|arg| arg == "--energy"),
+            day_night: args.iter().any(|arg| arg == "--day-night"),
+            levy_flight: args.iter().any(|arg| arg == "--levy"),
+            wall_following: args.iter().any(|arg| arg == "--wall-following"),
+            ..Default::default()
+        };
+        for (_, param) in args
+            .iter()
+            .zip(args.iter().skip(1))
+            .filter(|(arg, _)| *arg == "--set")
+        {
+            let (name, value) = param
+                .split_once('=')
+                .ok_or_else(|| format!("expected `name=value` in `{}`", param))?;
+            if !cfg.set_param(name, parse("number", value)?) {
+                return Err(format!("unknown parameter `{}`", name));
+            }
+        }
+
+        let ant_count = match arg_value(args, "--ants") {
+            Some(count) => parse("ant count", count)?,
+            None => ANT_COUNT,
+        };
+        let mut colony = Colony::new(vec2(width as f32 * 0.2, height as f32 * 0.5), ant_count);
+        if let Some(color) = arg_value(args, "--ant-color") {
+            let hex = u32::from_str_radix(color.trim_start_matches('#'), 16)
+                .map_err(|_| format!("invalid color `{}`", color))?;
+            colony.color = Color::from_hex(hex);
+        }
+        let mut world = World::new(width, height);
+        world.merge_markers = args.iter().any(|arg| arg == "--merge-markers");
+        let recorder = match arg_value(args, "--record") {
+            Some(path) => {
+                // the starting food and nest end up in the first frame
+                world.record_events();
+                Some(
+                    Recorder::create(path, &world, TIME_STEP)
+                        .map_err(|err| file_error(path, err))?,
+                )
+            }
+            None => None,
+        };
+        if let Some(path) = arg_value(args, "--terrain") {
+            world
+                .terrain
+                .load_map(path)
+                .map_err(|err| file_error(path, err))?;
+        }
+        let generate = args.iter().any(|arg| arg == "--generate");
+        if generate {
+            generate_map(&mut world, colony.position, &cfg);
+        }
+        colony.move_to(colony.position, &mut world);
+        let scenario = match arg_value(args, "--scenario") {
+            Some(path) => Some(Scenario::load(path).map_err(|err| file_error(path, err))?),
+            None => None,
+        };
+
+        // generated maps bring their own food
+        if !generate {
+            for x in 0..10 {
+                for y in 0..10 {
+                    world.add_food(Food::new(
+                        vec2(
+                            width as f32 * 0.8 + (x as f32 * 4.),
+                            height as f32 * 0.5 + (y as f32 * 4.),
+                        ),
+                        FoodKind::Seed,
+                        10.,
+                    ));
+                }
+            }
+        }
+
+        Ok(Self {
+            world,
+            colony,
+            cfg,
+            spawner: FoodSpawner::new(rand::rand()),
+            scenario,
+            recorder,
+            inputs: None,
+            replay: None,
+            tick: 0,
+            time: 0.0,
+        })
+    }
+
+    /// Rebuilds a recorded run, its inputs are applied as it steps
+    pub fn from_replay(replay: Replay) -> Result<Self, String> {
+        rand::srand(replay.seed);
+        let mut sim = Self::from_args(&replay.args)?;
+        sim.replay = Some(replay);
+        Ok(sim)
+    }
+
+    /// Applies a user input, recording it when asked to
+    pub fn input(&mut self, input: Input) {
+        if let Some(inputs) = self.inputs.as_mut() {
+            if let Err(err) = inputs.record(self.tick, &input) {
+                eprintln!("input recording stopped: {}", err);
+                self.inputs = None;
+            }
+        }
+        input.apply(self);
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay
+            .as_ref()
+            .is_some_and(|replay| replay.remaining() > 0)
+    }
+
+    /// Advances the simulation by a single time step
+    pub fn step(&mut self) {
+        while let Some(input) = self
+            .replay
+            .as_mut()
+            .and_then(|replay| replay.next(self.tick))
+        {
+            input.apply(self);
+        }
+        if let Some(scenario) = self.scenario.as_mut() {
+            scenario.apply(self.time, &mut self.world, &mut self.colony, &mut self.cfg);
+        }
+        self.spawner.update(TIME_STEP, &mut self.world, &self.cfg);
+        self.colony.update(TIME_STEP, &mut self.world, &self.cfg);
+        self.world.update(TIME_STEP, &self.cfg);
+        if let Some(recorder) = self.recorder.as_mut() {
+            let events = self.world.take_events();
+            // a failing recording doesn't stop the run
+            if let Err(err) = recorder.record(&self.colony, &events) {
+                eprintln!("recording stopped: {}", err);
+                self.recorder = None;
+                self.world.stop_events();
+            }
+        }
+        self.tick += 1;
+        self.time += TIME_STEP;
+    }
+}
+
+/// Parses an option value, naming what was expected when it fails
+fn parse<T: FromStr>(what: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {} `{}`", what, value))
+}
+
+fn file_error(path: &str, err: io::Error) -> String {
+    format!("{}: {}", path, err)
+}
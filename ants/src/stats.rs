@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+
+use crate::sim::{Colony, MarkerType, World};
+
+/// Sim seconds between two samples
+const SAMPLE_PERIOD: f32 = 0.5;
+/// Samples kept per chart
+const HISTORY: usize = 120;
+const CHART_WIDTH: f32 = 180.0;
+const CHART_HEIGHT: f32 = 40.0;
+
+struct Series {
+    label: &'static str,
+    color: Color,
+    values: VecDeque<f32>,
+}
+
+impl Series {
+    fn new(label: &'static str, color: Color) -> Self {
+        Self {
+            label,
+            color,
+            values: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    fn push(&mut self, value: f32) {
+        if self.values.len() == HISTORY {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    fn draw(&self, x: f32, y: f32) {
+        draw_rectangle(x, y, CHART_WIDTH, CHART_HEIGHT, Color::new(0., 0., 0., 0.6));
+        draw_rectangle_lines(x, y, CHART_WIDTH, CHART_HEIGHT, 1.0, DARKGRAY);
+
+        let max = self.values.iter().copied().fold(0.0f32, f32::max);
+        let scale = if max > 0.0 { CHART_HEIGHT / max } else { 0.0 };
+        let step = CHART_WIDTH / (HISTORY - 1) as f32;
+        let point =
+            |i: usize, value: f32| vec2(x + i as f32 * step, y + CHART_HEIGHT - value * scale);
+
+        for (i, (a, b)) in self
+            .values
+            .iter()
+            .zip(self.values.iter().skip(1))
+            .enumerate()
+        {
+            let (a, b) = (point(i, *a), point(i + 1, *b));
+            draw_line(a.x, a.y, b.x, b.y, 1.0, self.color);
+        }
+
+        let last = self.values.back().copied().unwrap_or(0.0);
+        draw_text(
+            &format!("{}: {:.2}", self.label, last),
+            x + 4.0,
+            y + 14.0,
+            16.0,
+            self.color,
+        );
+    }
+}
+
+/// Rolling charts of colony performance, drawn in the top right corner
+pub struct Stats {
+    timer: f32,
+    last_food: f32,
+    food_rate: Series,
+    population: Series,
+    home_markers: Series,
+    food_markers: Series,
+    carrying: Series,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            timer: 0.0,
+            last_food: 0.0,
+            food_rate: Series::new("food/s", GREEN),
+            population: Series::new("population", WHITE),
+            home_markers: Series::new("home markers", SKYBLUE),
+            food_markers: Series::new("food markers", LIME),
+            carrying: Series::new("carrying ratio", ORANGE),
+        }
+    }
+}
+
+impl Stats {
+    pub fn update(&mut self, dt: f32, world: &World, colony: &Colony) {
+        self.timer += dt;
+        if self.timer < SAMPLE_PERIOD {
+            return;
+        }
+
+        self.food_rate
+            .push((colony.delivered - self.last_food) / self.timer);
+        self.last_food = colony.delivered;
+        self.timer = 0.0;
+
+        let population = colony.population();
+        self.population.push(population as f32);
+        self.home_markers
+            .push(world.marker_count(MarkerType::ToHome) as f32);
+        self.food_markers
+            .push(world.marker_count(MarkerType::ToFood) as f32);
+        self.carrying.push(if population > 0 {
+            colony.carrying() as f32 / population as f32
+        } else {
+            0.0
+        });
+    }
+
+    pub fn draw(&self) {
+        let x = screen_width() - CHART_WIDTH - 10.0;
+        for (i, series) in [
+            &self.food_rate,
+            &self.population,
+            &self.home_markers,
+            &self.food_markers,
+            &self.carrying,
+        ]
+        .iter()
+        .enumerate()
+        {
+            series.draw(x, 10.0 + i as f32 * (CHART_HEIGHT + 5.0));
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+};
+
+use macroquad::rand;
+
+use crate::{arg_value, simulation::Simulation, Config, TIME_STEP};
+
+const DEFAULT_SEEDS: u64 = 5;
+const DEFAULT_DURATION: f32 = 60.0;
+
+/// Runs headless simulations over every combination of the swept parameter
+/// values, with the same seeds for each combination, and writes a table of
+/// the food delivered, the food patches found and the mean time taken to
+/// find them per combination:
+///
+/// ```text
+/// ants sweep --param marker_detection_radius=20,40,60 --param marker_period=0.25,0.5 \
+///     --seeds 5 --duration 120 --out sweep.tsv
+/// ```
+///
+/// Other options such as `--ants`, `--terrain` or `--set` apply to every run.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let axes = args
+        .iter()
+        .zip(args.iter().skip(1))
+        .filter(|(arg, _)| *arg == "--param")
+        .map(|(_, param)| parse_axis(param))
+        .collect::<Result<Vec<_>, _>>()?;
+    if axes.is_empty() {
+        return Err("nothing to sweep, add `--param name=value,value,...`".to_owned());
+    }
+
+    let seeds = match arg_value(args, "--seeds") {
+        Some(seeds) => seeds
+            .parse()
+            .map_err(|_| format!("invalid seed count `{}`", seeds))?,
+        None => DEFAULT_SEEDS,
+    };
+    let duration = match arg_value(args, "--duration") {
+        Some(duration) => duration
+            .parse()
+            .map_err(|_| format!("invalid duration `{}`", duration))?,
+        None => DEFAULT_DURATION,
+    };
+    let steps = (duration / TIME_STEP).round() as usize;
+
+    let mut out: Box<dyn Write> = match arg_value(args, "--out") {
+        Some(path) => Box::new(File::create(path).map_err(|err| err.to_string())?),
+        None => Box::new(io::stdout()),
+    };
+
+    let mut header: Vec<&str> = axes.iter().map(|(name, _)| name.as_str()).collect();
+    header.extend([
+        "runs",
+        "food_mean",
+        "food_variance",
+        "found_mean",
+        "discovery_mean",
+    ]);
+    writeln!(out, "{}", header.join("\t")).map_err(|err| err.to_string())?;
+
+    // odometer over the value index of each axis
+    let mut indices = vec![0; axes.len()];
+    loop {
+        let values: Vec<f32> = axes
+            .iter()
+            .zip(&indices)
+            .map(|((_, values), &i)| values[i])
+            .collect();
+
+        let runs: Vec<(f32, usize, Option<f32>)> = (0..seeds)
+            .map(|seed| {
+                rand::srand(seed);
+                // set before building the world, so map parameters apply too
+                let mut run_args = args.to_vec();
+                for ((name, _), value) in axes.iter().zip(&values) {
+                    run_args.push("--set".to_owned());
+                    run_args.push(format!("{}={}", name, value));
+                }
+                let mut sim = Simulation::from_args(&run_args)?;
+                for _ in 0..steps {
+                    sim.step();
+                }
+                Ok((
+                    sim.colony.delivered,
+                    sim.world.discoveries,
+                    sim.world.mean_discovery_time(),
+                ))
+            })
+            .collect::<Result<_, String>>()?;
+        let food: Vec<f32> = runs.iter().map(|(food, _, _)| *food).collect();
+        let (mean, variance) = mean_variance(&food);
+        let found: Vec<f32> = runs.iter().map(|(_, found, _)| *found as f32).collect();
+        let (found_mean, _) = mean_variance(&found);
+        // runs without any discovery are left out
+        let discovery: Vec<f32> = runs.iter().filter_map(|(_, _, time)| *time).collect();
+        let (discovery_mean, _) = mean_variance(&discovery);
+
+        let mut row: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        row.push(food.len().to_string());
+        row.push(format!("{:.3}", mean));
+        row.push(format!("{:.3}", variance));
+        row.push(format!("{:.3}", found_mean));
+        row.push(format!("{:.3}", discovery_mean));
+        writeln!(out, "{}", row.join("\t")).map_err(|err| err.to_string())?;
+        out.flush().map_err(|err| err.to_string())?;
+
+        let Some(axis) = (0..axes.len())
+            .rev()
+            .find(|&i| indices[i] + 1 < axes[i].1.len())
+        else {
+            break;
+        };
+        indices[axis] += 1;
+        indices[axis + 1..].fill(0);
+    }
+
+    Ok(())
+}
+
+/// Parses `name=value,value,...`
+fn parse_axis(param: &str) -> Result<(String, Vec<f32>), String> {
+    let (name, values) = param
+        .split_once('=')
+        .ok_or_else(|| format!("expected `name=values` in `{}`", param))?;
+    if !Config::PARAMS.contains(&name) {
+        return Err(format!("unknown parameter `{}`", name));
+    }
+
+    let values = values
+        .split(',')
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid number `{}`", value))
+        })
+        .collect::<Result<Vec<f32>, _>>()?;
+    Ok((name.to_owned(), values))
+}
+
+/// Mean and unbiased sample variance
+fn mean_variance(samples: &[f32]) -> (f32, f32) {
+    let n = samples.len() as f32;
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let mean = samples.iter().sum::<f32>() / n;
+    let variance = if samples.len() > 1 {
+        samples.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / (n - 1.0)
+    } else {
+        0.0
+    };
+    (mean, variance)
+}
@@ -0,0 +1,193 @@
+use macroquad::prelude::*;
+
+use crate::sim::{Colony, Food, FoodKind, TerrainKind, World};
+
+const BUTTON_WIDTH: f32 = 90.0;
+const BUTTON_HEIGHT: f32 = 24.0;
+const MAX_BRUSH_RADIUS: f32 = 200.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    Food,
+    Eraser,
+    Terrain,
+    Wall,
+    MoveNest,
+    SpawnAnts,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 6] = [
+        Tool::Food,
+        Tool::Eraser,
+        Tool::Terrain,
+        Tool::Wall,
+        Tool::MoveNest,
+        Tool::SpawnAnts,
+    ];
+
+    const KEYS: [KeyCode; 6] = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Food => "food",
+            Tool::Eraser => "eraser",
+            Tool::Terrain => "terrain",
+            Tool::Wall => "wall",
+            Tool::MoveNest => "nest",
+            Tool::SpawnAnts => "ants",
+        }
+    }
+
+    /// Brushes are applied continuously while the button is held
+    fn is_brush(&self) -> bool {
+        matches!(self, Tool::Eraser | Tool::Terrain | Tool::Wall)
+    }
+}
+
+/// Mouse tools for editing the world, selected with the number keys or the
+/// buttons along the bottom of the window.
+#[derive(Clone)]
+pub struct Palette {
+    pub tool: Tool,
+    pub radius: f32,
+    pub quantity: f32,
+    pub food_kind: FoodKind,
+    pub terrain_kind: TerrainKind,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            tool: Tool::Food,
+            radius: 20.0,
+            quantity: 100.0,
+            food_kind: FoodKind::Seed,
+            terrain_kind: TerrainKind::Mud,
+        }
+    }
+}
+
+impl Palette {
+    fn button_rect(i: usize) -> Rect {
+        Rect::new(
+            10.0 + i as f32 * (BUTTON_WIDTH + 5.0),
+            screen_height() - BUTTON_HEIGHT - 10.0,
+            BUTTON_WIDTH,
+            BUTTON_HEIGHT,
+        )
+    }
+
+    /// Handles tool selection, returns `true` when the selected tool should
+    /// be applied under the mouse.
+    pub fn update(&mut self) -> bool {
+        for (tool, key) in Tool::ALL.iter().zip(Tool::KEYS) {
+            if is_key_pressed(key) {
+                self.tool = *tool;
+            }
+        }
+        if is_key_pressed(KeyCode::F) {
+            self.food_kind = self.food_kind.next();
+        }
+        if is_key_pressed(KeyCode::T) {
+            self.terrain_kind = self.terrain_kind.next();
+        }
+        if is_key_pressed(KeyCode::Equal) {
+            self.quantity = (self.quantity * 2.0).min(10000.0);
+        }
+        if is_key_pressed(KeyCode::Minus) {
+            self.quantity = (self.quantity * 0.5).max(1.0);
+        }
+
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 {
+            self.radius = (self.radius + wheel.signum() * 5.0).clamp(0.0, MAX_BRUSH_RADIUS);
+        }
+
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let screen_pos = Vec2::from(mouse_position());
+            if let Some(i) =
+                (0..Tool::ALL.len()).find(|&i| Self::button_rect(i).contains(screen_pos))
+            {
+                self.tool = Tool::ALL[i];
+                return false;
+            }
+        }
+
+        if self.tool.is_brush() {
+            is_mouse_button_down(MouseButton::Left)
+        } else {
+            is_mouse_button_pressed(MouseButton::Left)
+        }
+    }
+
+    pub fn apply(&self, pos: Vec2, world: &mut World, colony: &mut Colony) {
+        match self.tool {
+            Tool::Food => {
+                // sources packed side by side across the brush
+                let spacing = self.food_kind.radius() * 2.0;
+                let steps = (self.radius / spacing) as i32;
+                for x in -steps..=steps {
+                    for y in -steps..=steps {
+                        let offset = vec2(x as f32, y as f32) * spacing;
+                        if offset.length() <= self.radius {
+                            world.add_food(Food::new(pos + offset, self.food_kind, self.quantity));
+                        }
+                    }
+                }
+            }
+            Tool::Eraser => world.erase(pos, self.radius),
+            Tool::Terrain => world.terrain.paint(pos, self.radius, self.terrain_kind),
+            Tool::Wall => world.terrain.paint(pos, self.radius, TerrainKind::Wall),
+            Tool::MoveNest => colony.move_to(pos, world),
+            Tool::SpawnAnts => colony.spawn(pos, self.quantity as usize),
+        }
+    }
+
+    /// Draws the brush outline under the cursor, in world coordinates
+    pub fn draw_preview(&self, pos: Vec2) {
+        let (radius, color) = match self.tool {
+            Tool::Food => (self.radius, self.food_kind.color()),
+            Tool::Eraser => (self.radius, WHITE),
+            Tool::Terrain => (self.radius, YELLOW),
+            Tool::Wall => (self.radius, GRAY),
+            Tool::MoveNest => (10.0, DARKBLUE),
+            Tool::SpawnAnts => (5.0, RED),
+        };
+        draw_circle_lines(pos.x, pos.y, radius.max(1.0), 1.0, color);
+    }
+
+    pub fn draw_buttons(&self) {
+        for (i, tool) in Tool::ALL.iter().enumerate() {
+            let rect = Self::button_rect(i);
+            let color = if *tool == self.tool { DARKGRAY } else { BLACK };
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, color);
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, GRAY);
+            draw_text(
+                &format!("{} {}", i + 1, tool.name()),
+                rect.x + 6.0,
+                rect.y + 17.0,
+                20.0,
+                WHITE,
+            );
+        }
+
+        draw_text(
+            &format!(
+                "radius: {} quantity: {} food: {:?} terrain: {:?}",
+                self.radius, self.quantity, self.food_kind, self.terrain_kind
+            ),
+            10.0,
+            screen_height() - BUTTON_HEIGHT - 20.0,
+            20.0,
+            WHITE,
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use macroquad::prelude::*;
+
+use crate::{
+    export::FieldSnapshot,
+    sim::{Colony, MarkerType, TerrainKind, World},
+    TERRAIN_CELL_SIZE,
+};
+
+/// Sim seconds between two extractions
+const UPDATE_PERIOD: f32 = 1.0;
+/// Food sources closer than this to a cluster's first source join it
+const FOOD_CLUSTER_RADIUS: f32 = 60.0;
+/// Extra cost of crossing a cell without markers, relative to a cell on a
+/// saturated trail
+const OFF_TRAIL_COST: f32 = 10.0;
+/// Share of the reference intensity for a cell to count as trail
+const TRAIL_THRESHOLD: f32 = 0.1;
+/// Percentile of the marked cells taken as a saturated trail, lower than the
+/// export one as the busiest trails should all cost the same to follow
+const REFERENCE_PERCENTILE: f32 = 0.9;
+/// Share of a trail that must run over marked cells for it to count as an
+/// actual trail, below it the path mostly follows the off trail costs
+const MIN_COVERAGE: f32 = 0.5;
+/// Max distance from the raw path when simplifying it to a polyline
+const SIMPLIFY_TOLERANCE: f32 = 5.0;
+
+/// Dominant trail from the nest to a food cluster, with the obstacle-aware
+/// shortest path to compare it with
+pub struct Trail {
+    pub food: Vec2,
+    pub path: Vec<Vec2>,
+    pub shortest: Vec<Vec2>,
+    /// Share of the trail running over marked cells
+    pub coverage: f32,
+}
+
+impl Trail {
+    pub fn length(&self) -> f32 {
+        polyline_length(&self.path)
+    }
+
+    pub fn shortest_length(&self) -> f32 {
+        polyline_length(&self.shortest)
+    }
+
+    pub fn straight_length(&self) -> f32 {
+        self.path[0].distance(self.food)
+    }
+
+    /// Whether enough of the trail runs over markers to be a trail at all
+    pub fn is_established(&self) -> bool {
+        self.coverage >= MIN_COVERAGE
+    }
+
+    /// Shortest over trail length, 1 for an optimal trail. `None` for a
+    /// trail not established yet, its path is mostly the shortest one.
+    pub fn efficiency(&self) -> Option<f32> {
+        self.is_established()
+            .then(|| self.shortest_length() / self.length().max(f32::EPSILON))
+    }
+}
+
+/// `ToFood` marker intensity and walls sampled on the terrain cells
+struct Field {
+    width: usize,
+    height: usize,
+    cell_size: f32,
+    intensity: Vec<f32>,
+    walls: Vec<bool>,
+    reference: f32,
+}
+
+impl Field {
+    fn new(world: &World) -> Self {
+        let markers = FieldSnapshot::new(world, MarkerType::ToFood, TERRAIN_CELL_SIZE, 0.0);
+        let (width, height, cell_size) = (markers.width, markers.height, markers.cell_size);
+
+        let walls = (0..width * height)
+            .map(|i| {
+                let center = vec2((i % width) as f32 + 0.5, (i / width) as f32 + 0.5);
+                world.terrain.get(center * cell_size as f32) == TerrainKind::Wall
+            })
+            .collect();
+
+        let reference = match markers.percentile(REFERENCE_PERCENTILE) {
+            reference if reference > 0.0 => reference,
+            _ => 1.0,
+        };
+        let intensity = markers.values;
+
+        Self {
+            width,
+            height,
+            cell_size: cell_size as f32,
+            intensity,
+            walls,
+            reference,
+        }
+    }
+
+    fn cell(&self, pos: Vec2) -> usize {
+        let x = ((pos.x / self.cell_size) as usize).min(self.width - 1);
+        let y = ((pos.y / self.cell_size) as usize).min(self.height - 1);
+        y * self.width + x
+    }
+
+    fn center(&self, cell: usize) -> Vec2 {
+        vec2(
+            (cell % self.width) as f32 + 0.5,
+            (cell / self.width) as f32 + 0.5,
+        ) * self.cell_size
+    }
+
+    /// Marker intensity relative to a saturated trail, in `[0, 1]`
+    fn strength(&self, cell: usize) -> f32 {
+        (self.intensity[cell] / self.reference).min(1.0)
+    }
+
+    /// Dijkstra from `start` over the non wall cells, each step weighted by
+    /// `cost` of the cell entered. Returns the previous cell of each cell on
+    /// its cheapest path.
+    fn search(&self, start: usize, cost: impl Fn(usize) -> f32) -> Vec<Option<usize>> {
+        let mut dist = vec![f32::INFINITY; self.intensity.len()];
+        let mut prev = vec![None; self.intensity.len()];
+        let mut queue = BinaryHeap::new();
+        dist[start] = 0.0;
+        queue.push(Node(0.0, start));
+
+        while let Some(Node(d, cell)) = queue.pop() {
+            if d > dist[cell] {
+                continue;
+            }
+
+            let (x, y) = ((cell % self.width) as i32, (cell / self.width) as i32);
+            for (dx, dy) in [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ] {
+                let free = |x: i32, y: i32| {
+                    x >= 0
+                        && y >= 0
+                        && (x as usize) < self.width
+                        && (y as usize) < self.height
+                        && !self.walls[y as usize * self.width + x as usize]
+                };
+                // no cutting wall corners on diagonals
+                if !free(x + dx, y + dy) || !free(x + dx, y) || !free(x, y + dy) {
+                    continue;
+                }
+
+                let next = (y + dy) as usize * self.width + (x + dx) as usize;
+                let step = if dx != 0 && dy != 0 {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let d = d + step * self.cell_size * cost(next);
+                if d < dist[next] {
+                    dist[next] = d;
+                    prev[next] = Some(cell);
+                    queue.push(Node(d, next));
+                }
+            }
+        }
+
+        prev
+    }
+
+    /// Cells from `start` to `goal` following `prev`, if `goal` was reached
+    fn path(&self, prev: &[Option<usize>], start: usize, goal: usize) -> Option<Vec<usize>> {
+        let mut cells = vec![goal];
+        while let Some(&cell) = cells.last() {
+            if cell == start {
+                cells.reverse();
+                return Some(cells);
+            }
+            cells.push(prev[cell]?);
+        }
+        None
+    }
+}
+
+/// Queue entry ordered by lowest distance first
+struct Node(f32, usize);
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0)
+    }
+}
+
+/// Trail network between the nest and the food, extracted from the `ToFood`
+/// markers every second while shown
+pub struct Trails {
+    timer: f32,
+    trails: Vec<Trail>,
+}
+
+impl Default for Trails {
+    fn default() -> Self {
+        Self {
+            // extract right away the first time
+            timer: UPDATE_PERIOD,
+            trails: vec![],
+        }
+    }
+}
+
+impl Trails {
+    pub fn update(&mut self, dt: f32, world: &World, colony: &Colony) {
+        self.timer += dt;
+        if self.timer >= UPDATE_PERIOD {
+            self.trails = extract(world, colony);
+            self.timer = 0.0;
+        }
+    }
+
+    /// Draws the trails and shortest paths, in world coordinates
+    pub fn draw(&self) {
+        for trail in &self.trails {
+            draw_polyline(&trail.shortest, 1.0, SKYBLUE);
+            if trail.is_established() {
+                draw_polyline(&trail.path, 2.0, YELLOW);
+            }
+        }
+    }
+
+    /// Lists the length of each trail next to the straight line and
+    /// shortest path, in screen coordinates
+    pub fn draw_report(&self) {
+        for (i, trail) in self.trails.iter().enumerate() {
+            let efficiency = match trail.efficiency() {
+                Some(efficiency) => format!("{:.2}", efficiency),
+                None => "no trail".to_owned(),
+            };
+            draw_text(
+                &format!(
+                    "trail to {:.0},{:.0}: {:.0}px straight: {:.0}px shortest: {:.0}px efficiency: {} coverage: {:.0}%",
+                    trail.food.x,
+                    trail.food.y,
+                    trail.length(),
+                    trail.straight_length(),
+                    trail.shortest_length(),
+                    efficiency,
+                    trail.coverage * 100.0
+                ),
+                20.0,
+                100.0 + i as f32 * 15.0,
+                20.0,
+                YELLOW,
+            );
+        }
+    }
+}
+
+/// Extracts the dominant trail from the nest to each food cluster
+pub fn extract(world: &World, colony: &Colony) -> Vec<Trail> {
+    let field = Field::new(world);
+    let start = field.cell(colony.position);
+    let along_trails = field.search(start, |cell| {
+        1.0 + OFF_TRAIL_COST * (1.0 - field.strength(cell))
+    });
+    let shortest = field.search(start, |_| 1.0);
+
+    let to_polyline = |cells: Vec<usize>, food: Vec2| {
+        let mut points: Vec<Vec2> = cells.into_iter().map(|cell| field.center(cell)).collect();
+        let last = points.len() - 1;
+        points[0] = colony.position;
+        points[last] = food;
+        simplify(&points, SIMPLIFY_TOLERANCE)
+    };
+
+    food_clusters(world)
+        .into_iter()
+        .filter_map(|food| {
+            let goal = field.cell(food);
+            let path = field.path(&along_trails, start, goal)?;
+            let marked = path
+                .iter()
+                .filter(|cell| field.strength(**cell) >= TRAIL_THRESHOLD)
+                .count();
+            let shortest = field.path(&shortest, start, goal)?;
+
+            Some(Trail {
+                food,
+                coverage: marked as f32 / path.len() as f32,
+                path: to_polyline(path, food),
+                shortest: to_polyline(shortest, food),
+            })
+        })
+        .collect()
+}
+
+/// Centers of the groups of nearby food sources still holding items
+fn food_clusters(world: &World) -> Vec<Vec2> {
+    // first source of each cluster, sum of positions and count
+    let mut clusters: Vec<(Vec2, Vec2, f32)> = vec![];
+    for food in world.grid_food.iter().filter(|food| food.has_items()) {
+        match clusters
+            .iter_mut()
+            .find(|(first, _, _)| first.distance(food.position) < FOOD_CLUSTER_RADIUS)
+        {
+            Some((_, sum, count)) => {
+                *sum += food.position;
+                *count += 1.0;
+            }
+            None => clusters.push((food.position, food.position, 1.0)),
+        }
+    }
+
+    clusters
+        .into_iter()
+        .map(|(_, sum, count)| sum / count)
+        .collect()
+}
+
+fn polyline_length(points: &[Vec2]) -> f32 {
+    points
+        .iter()
+        .zip(points.iter().skip(1))
+        .map(|(a, b)| a.distance(*b))
+        .sum()
+}
+
+fn draw_polyline(points: &[Vec2], thickness: f32, color: Color) {
+    for (a, b) in points.iter().zip(points.iter().skip(1)) {
+        draw_line(a.x, a.y, b.x, b.y, thickness, color);
+    }
+}
+
+/// Ramer-Douglas-Peucker simplification keeping points further than
+/// `tolerance` from the simplified line
+fn simplify(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let line = last - first;
+    let distance = |p: Vec2| {
+        if line.length() > 0.0 {
+            line.perp_dot(p - first).abs() / line.length()
+        } else {
+            p.distance(first)
+        }
+    };
+    let (index, max) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, distance(*p)))
+        .fold((0, 0.0), |a, b| if b.1 > a.1 { b } else { a });
+
+    if max > tolerance {
+        let mut left = simplify(&points[..=index], tolerance);
+        left.pop();
+        left.extend(simplify(&points[index..], tolerance));
+        left
+    } else {
+        vec![first, last]
+    }
+}